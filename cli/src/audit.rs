@@ -0,0 +1,237 @@
+use crate::target_dir::BuildScriptRun;
+use serde_json::{json, Value};
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+/// Directory names which are never worth tracking as a whole.
+const HUGE_DIRECTORY_NAMES: &[&str] = &["node_modules", "target", ".git", "bower_components"];
+
+/// A problem with change detection instructions of a build script.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// No `rerun-if-changed` instructions, cargo reruns the build script on every package change.
+    NoRerunIfChanged,
+    /// A tracked path does not exist, cargo reruns the build script on every build.
+    MissingPath(PathBuf),
+    /// A tracked directory is too big to be checked on every build.
+    HugeDirectory { path: PathBuf, files: Option<usize> },
+    /// A tracked path lies outside of the package.
+    OutsidePackage(PathBuf),
+}
+
+/// Limits used by the audit.
+pub struct Audit {
+    /// Directories with more files are reported as huge.
+    pub max_files: usize,
+}
+
+impl Audit {
+    /// Finds all problems of `run`.
+    pub fn findings(&self, run: &BuildScriptRun) -> io::Result<Vec<Finding>> {
+        if run.rerun_if_changed.is_empty() {
+            return Ok(vec![Finding::NoRerunIfChanged]);
+        }
+
+        let mut result = vec![];
+
+        for path in &run.rerun_if_changed {
+            let resolved = run.resolve(path);
+
+            if let Some(root) = &run.package_root {
+                if !normalize(&resolved).starts_with(normalize(root)) {
+                    result.push(Finding::OutsidePackage(path.clone()));
+                }
+            }
+
+            if fs::symlink_metadata(&resolved).is_err() {
+                result.push(Finding::MissingPath(path.clone()));
+            } else if resolved.is_dir() {
+                let named_huge = path.components().any(|component| match component {
+                    Component::Normal(name) => {
+                        HUGE_DIRECTORY_NAMES.iter().any(|huge| name == *huge)
+                    }
+                    _ => false,
+                });
+
+                if named_huge {
+                    result.push(Finding::HugeDirectory {
+                        path: path.clone(),
+                        files: None,
+                    });
+                } else if let Some(files) = count_files(&resolved, self.max_files)? {
+                    result.push(Finding::HugeDirectory {
+                        path: path.clone(),
+                        files: Some(files),
+                    });
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl Finding {
+    /// A stable identifier of the finding kind.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Finding::NoRerunIfChanged => "no-rerun-if-changed",
+            Finding::MissingPath(_) => "missing-path",
+            Finding::HugeDirectory { .. } => "huge-directory",
+            Finding::OutsidePackage(_) => "outside-package",
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Finding::NoRerunIfChanged => None,
+            Finding::MissingPath(path)
+            | Finding::HugeDirectory { path, .. }
+            | Finding::OutsidePackage(path) => Some(path),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "kind": self.kind(),
+            "path": self.path().map(|path| path.to_string_lossy()),
+            "message": self.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::NoRerunIfChanged => write!(
+                f,
+                "no rerun-if-changed instructions, the build script reruns on every package change"
+            ),
+            Finding::MissingPath(path) => write!(
+                f,
+                "{} does not exist, the build script reruns on every build",
+                path.display()
+            ),
+            Finding::HugeDirectory {
+                path,
+                files: Some(files),
+            } => write!(
+                f,
+                "{} contains more than {} files, all of them are checked on every build",
+                path.display(),
+                files
+            ),
+            Finding::HugeDirectory { path, files: None } => write!(
+                f,
+                "{} is a dependency or build output directory and should not be tracked",
+                path.display()
+            ),
+            Finding::OutsidePackage(path) => {
+                write!(f, "{} lies outside of the package", path.display())
+            }
+        }
+    }
+}
+
+/// Counts files in `dir`, returns the count only if it exceeds `limit`.
+fn count_files(dir: &Path, limit: usize) -> io::Result<Option<usize>> {
+    let mut count = 0;
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pending.push(entry.path());
+            } else {
+                count += 1;
+                if count > limit {
+                    return Ok(Some(limit));
+                }
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Lexically resolves `.` and `..` components.
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Audit, Finding};
+    use crate::target_dir::BuildScriptRun;
+    use std::{collections::BTreeMap, env, fs, path::PathBuf, time::SystemTime};
+
+    #[test]
+    fn finds_problems() {
+        let root = env::temp_dir().join(format!(
+            "cargo-change-detection-audit-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("web/node_modules")).unwrap();
+        fs::create_dir_all(root.join("static")).unwrap();
+        for i in 0..3 {
+            fs::write(root.join("static").join(i.to_string()), "").unwrap();
+        }
+
+        let run = |paths: &[&str]| BuildScriptRun {
+            package: "web".into(),
+            dir: root.join("target/debug/build/web-0123"),
+            package_root: Some(root.clone()),
+            invoked: Some(SystemTime::now()),
+            rerun_if_changed: paths.iter().map(PathBuf::from).collect(),
+            rerun_if_env_changed: vec![],
+            env_values: BTreeMap::new(),
+        };
+
+        let audit = Audit { max_files: 2 };
+
+        assert_eq!(
+            audit.findings(&run(&[])).unwrap(),
+            vec![Finding::NoRerunIfChanged]
+        );
+        assert_eq!(
+            audit
+                .findings(&run(&[
+                    "web/dist",
+                    "web/node_modules",
+                    "static",
+                    "../shared"
+                ]))
+                .unwrap(),
+            vec![
+                Finding::MissingPath("web/dist".into()),
+                Finding::HugeDirectory {
+                    path: "web/node_modules".into(),
+                    files: None
+                },
+                Finding::HugeDirectory {
+                    path: "static".into(),
+                    files: Some(2)
+                },
+                Finding::OutsidePackage("../shared".into()),
+                Finding::MissingPath("../shared".into()),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use anyhow::{anyhow, Result};
+use audit::Audit;
+use pico_args::Arguments;
+use serde_json::json;
+use std::{env, ffi::OsString, path::PathBuf, process, time::Duration};
+use target_dir::Layout;
+
+mod audit;
+mod target_dir;
+mod watch;
+mod why;
+
+const USAGE: &str = "\
+cargo change-detection
+Inspect change detection instructions of build scripts.
+USAGE:
+    cargo change-detection <SUBCOMMAND> [OPTIONS]
+    cargo change-detection watch [WATCH OPTIONS] -- <CARGO ARGS>
+SUBCOMMANDS:
+    why      Report build scripts which will rerun on the next build and why
+    audit    Report build scripts with problematic change detection instructions
+    watch    Run cargo every time a path tracked by a workspace build script changes
+OPTIONS:
+    --target-dir <DIR>    Target directory, defaults to CARGO_TARGET_DIR or <workspace>/target
+    --target <TRIPLE>     Target triple the build scripts were built for
+    --profile <NAME>      Build profile, defaults to dev
+    --release             Same as --profile release
+AUDIT OPTIONS:
+    --all                 Audit build scripts of dependencies outside of the workspace too
+    --max-files <COUNT>   Report tracked directories with more files, defaults to 10000
+    --json                Print findings as JSON
+    --deny                Exit with a non-zero status if there are findings
+WATCH OPTIONS:
+    --debounce <MS>       Quiet period after a change before running cargo, defaults to 200
+    Target directory, target and profile are taken from cargo arguments";
+
+fn layout(args: &mut Arguments) -> Result<Layout> {
+    let target_dir: Option<PathBuf> = args.opt_value_from_str("--target-dir")?;
+    let target: Option<String> = args.opt_value_from_str("--target")?;
+    let release = args.contains("--release");
+    let profile: String = args
+        .opt_value_from_str("--profile")?
+        .unwrap_or_else(|| if release { "release" } else { "dev" }.to_owned());
+
+    Layout::new(target_dir, target, &profile)
+}
+
+fn finish(args: Arguments) -> Result<()> {
+    let remaining = args.finish();
+    if !remaining.is_empty() {
+        return Err(anyhow!("unexpected arguments: {:?}", remaining));
+    }
+
+    Ok(())
+}
+
+fn why(mut args: Arguments) -> Result<()> {
+    let layout = layout(&mut args)?;
+    finish(args)?;
+
+    let runs = layout.build_script_runs()?;
+    let mut rerun = 0;
+
+    for run in &runs {
+        let reasons = why::reasons(run)?;
+        if reasons.is_empty() {
+            continue;
+        }
+
+        rerun += 1;
+        println!("{} ({}) will rerun:", run.package, run.name());
+        for reason in reasons {
+            println!("    {}", reason);
+        }
+    }
+
+    println!("{} of {} build scripts will rerun", rerun, runs.len());
+
+    Ok(())
+}
+
+fn audit(mut args: Arguments) -> Result<()> {
+    let layout = layout(&mut args)?;
+    let all = args.contains("--all");
+    let json = args.contains("--json");
+    let deny = args.contains("--deny");
+    let audit = Audit {
+        max_files: args.opt_value_from_str("--max-files")?.unwrap_or(10_000),
+    };
+    finish(args)?;
+
+    let mut reports = vec![];
+    let mut total = 0;
+
+    for run in layout.build_script_runs()? {
+        let in_workspace = run
+            .package_root
+            .as_ref()
+            .is_some_and(|root| root.starts_with(&layout.workspace_root));
+        if !all && !in_workspace {
+            continue;
+        }
+
+        let findings = audit.findings(&run)?;
+        total += findings.len();
+        reports.push((run, findings));
+    }
+
+    if json {
+        let build_scripts = reports
+            .iter()
+            .map(|(run, findings)| {
+                json!({
+                    "package": run.package,
+                    "dir": run.dir.to_string_lossy(),
+                    "package_root": run.package_root.as_ref().map(|root| root.to_string_lossy()),
+                    "findings": findings.iter().map(|finding| finding.to_json()).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&json!({ "build_scripts": build_scripts }))?
+        );
+    } else {
+        for (run, findings) in &reports {
+            if findings.is_empty() {
+                continue;
+            }
+
+            println!("{} ({}):", run.package, run.name());
+            for finding in findings {
+                println!("    {}: {}", finding.kind(), finding);
+            }
+        }
+
+        println!("{} findings in {} build scripts", total, reports.len());
+    }
+
+    if deny && total > 0 {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn watch(mut args: Arguments, cargo_args: Vec<OsString>) -> Result<()> {
+    let debounce = args.opt_value_from_str("--debounce")?.unwrap_or(200);
+    finish(args)?;
+
+    if cargo_args.is_empty() {
+        return Err(anyhow!(
+            "cargo arguments are missing, for example: -- build"
+        ));
+    }
+
+    let mut cargo = Arguments::from_vec(cargo_args.clone());
+    let layout = layout(&mut cargo)?;
+
+    watch::watch(&layout, &cargo_args, Duration::from_millis(debounce))
+}
+
+fn main() -> Result<()> {
+    let mut args: Vec<OsString> = env::args_os().skip(1).collect();
+    let cargo_args = match args.iter().position(|arg| arg == "--") {
+        Some(index) => {
+            let cargo_args = args.split_off(index + 1);
+            args.pop();
+            cargo_args
+        }
+        None => vec![],
+    };
+
+    let mut args = Arguments::from_vec(args);
+    let mut subcommand = args.subcommand()?.unwrap_or_default();
+
+    // `cargo change-detection why` runs `cargo-change-detection change-detection why`
+    if subcommand == "change-detection" {
+        subcommand = args.subcommand()?.unwrap_or_default();
+    }
+
+    match subcommand.as_str() {
+        "why" => why(args)?,
+        "audit" => audit(args)?,
+        "watch" => watch(args, cargo_args)?,
+        "" => eprintln!("{}", USAGE),
+        unknown => {
+            eprintln!("unknown subcommand '{}'\n\n{}", unknown, USAGE);
+            process::exit(2);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,397 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Location of build script outputs in a cargo target directory.
+pub struct Layout {
+    /// A directory relative dep-info paths are resolved against.
+    pub workspace_root: PathBuf,
+    /// A profile directory, for example `target/debug`.
+    pub profile_dir: PathBuf,
+}
+
+/// A single build script run recorded in a target directory.
+pub struct BuildScriptRun {
+    /// A package name.
+    pub package: String,
+    /// A run directory, for example `target/debug/build/foo-0123456789abcdef`.
+    pub dir: PathBuf,
+    /// A package root directory, if it could be detected.
+    pub package_root: Option<PathBuf>,
+    /// A time of the last run, taken from `invoked.timestamp`.
+    pub invoked: Option<SystemTime>,
+    /// Paths from `rerun-if-changed` instructions, relative to the package root.
+    pub rerun_if_changed: Vec<PathBuf>,
+    /// Variables from `rerun-if-env-changed` instructions.
+    pub rerun_if_env_changed: Vec<String>,
+    /// Values of `rerun-if-env-changed` variables seen by cargo during the last run.
+    pub env_values: BTreeMap<String, Option<String>>,
+}
+
+impl Layout {
+    pub fn new(
+        target_dir: Option<PathBuf>,
+        target: Option<String>,
+        profile: &str,
+    ) -> Result<Layout> {
+        let current_dir = env::current_dir()?;
+        let workspace_root = workspace_root(&current_dir);
+
+        let target_dir = target_dir
+            .or_else(|| env::var_os("CARGO_TARGET_DIR").map(PathBuf::from))
+            .unwrap_or_else(|| workspace_root.join("target"));
+
+        let mut profile_dir = target_dir;
+        if let Some(target) = target {
+            profile_dir.push(target);
+        }
+        profile_dir.push(profile_dir_name(profile));
+
+        Ok(Layout {
+            workspace_root,
+            profile_dir,
+        })
+    }
+
+    /// Reads all build script runs, sorted by package name.
+    pub fn build_script_runs(&self) -> Result<Vec<BuildScriptRun>> {
+        let build_dir = self.profile_dir.join("build");
+        let entries = fs::read_dir(&build_dir)
+            .with_context(|| format!("can't read build directory {}", build_dir.display()))?;
+
+        let mut compiled = vec![];
+        let mut runs = vec![];
+
+        for entry in entries {
+            let dir = entry?.path();
+            if dir.join("output").is_file() {
+                runs.push(dir);
+            } else if dir.is_dir() {
+                compiled.push(dir);
+            }
+        }
+
+        // Runs are matched to compiled build scripts by fingerprint, names are ambiguous
+        // with several versions of a package or packages of the same name at different paths.
+        let mut roots_by_fingerprint = BTreeMap::new();
+        let mut roots_by_name: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for dir in compiled {
+            if let Some(root) = self.package_root(&dir)? {
+                if let Some(fingerprint) = self.fingerprint(&dir, "build-script-build-script-build")
+                {
+                    roots_by_fingerprint.insert(fingerprint, root.clone());
+                }
+                let roots = roots_by_name.entry(package_name(&dir)).or_default();
+                if !roots.contains(&root) {
+                    roots.push(root);
+                }
+            }
+        }
+
+        let mut result = runs
+            .into_iter()
+            .map(|dir| {
+                let package = package_name(&dir);
+                let fingerprint = self.run_fingerprint(&dir);
+                let package_root = fingerprint
+                    .as_ref()
+                    .and_then(build_script_fingerprint)
+                    .and_then(|hash| roots_by_fingerprint.get(&hash))
+                    .or_else(|| match roots_by_name.get(&package).map(Vec::as_slice) {
+                        Some([root]) => Some(root),
+                        _ => None,
+                    })
+                    .cloned();
+                self.read_run(dir, package, package_root, fingerprint)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        result.sort_by(|a, b| (&a.package, &a.dir).cmp(&(&b.package, &b.dir)));
+
+        Ok(result)
+    }
+
+    fn read_run(
+        &self,
+        dir: PathBuf,
+        package: String,
+        package_root: Option<PathBuf>,
+        fingerprint: Option<Value>,
+    ) -> Result<BuildScriptRun> {
+        let output = fs::read(dir.join("output"))
+            .with_context(|| format!("can't read {}", dir.join("output").display()))?;
+
+        let mut rerun_if_changed = vec![];
+        let mut rerun_if_env_changed = vec![];
+        for (key, value) in instructions(&String::from_utf8_lossy(&output)) {
+            match key {
+                "rerun-if-changed" => rerun_if_changed.push(PathBuf::from(value)),
+                "rerun-if-env-changed" => rerun_if_env_changed.push(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        let invoked = fs::metadata(dir.join("invoked.timestamp"))
+            .or_else(|_| fs::metadata(dir.join("output")))
+            .and_then(|metadata| metadata.modified())
+            .ok();
+
+        let env_values = fingerprint
+            .map(|fingerprint| env_values(&fingerprint))
+            .unwrap_or_default();
+
+        Ok(BuildScriptRun {
+            package,
+            dir,
+            package_root,
+            invoked,
+            rerun_if_changed,
+            rerun_if_env_changed,
+            env_values,
+        })
+    }
+
+    /// Reads the fingerprint of the build script run in `dir`.
+    fn run_fingerprint(&self, dir: &Path) -> Option<Value> {
+        let json = fs::read(
+            self.fingerprint_dir(dir)?
+                .join("run-build-script-build-script-build.json"),
+        )
+        .ok()?;
+
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Reads the fingerprint hash of the unit `name` built in `dir`.
+    fn fingerprint(&self, dir: &Path, name: &str) -> Option<String> {
+        let hash = fs::read_to_string(self.fingerprint_dir(dir)?.join(name)).ok()?;
+
+        Some(hash.trim().to_owned())
+    }
+
+    fn fingerprint_dir(&self, dir: &Path) -> Option<PathBuf> {
+        Some(self.profile_dir.join(".fingerprint").join(dir.file_name()?))
+    }
+
+    /// Detects a package root from the dep-info file of a compiled build script.
+    fn package_root(&self, dir: &Path) -> Result<Option<PathBuf>> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "d") {
+                let dep_info = fs::read_to_string(&path)?;
+                let build_rs = match first_dependency(&dep_info) {
+                    Some(build_rs) => self.workspace_root.join(build_rs),
+                    None => continue,
+                };
+
+                return Ok(build_rs
+                    .ancestors()
+                    .skip(1)
+                    .find(|dir| dir.join("Cargo.toml").is_file())
+                    .map(Path::to_path_buf));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl BuildScriptRun {
+    /// Resolves a path from a `rerun-if-changed` instruction.
+    pub fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.package_root {
+            Some(root) => root.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    /// A run directory name, for example `foo-0123456789abcdef`.
+    pub fn name(&self) -> String {
+        self.dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Parses `cargo:key=value` and `cargo::key=value` instructions.
+pub fn instructions(output: &str) -> impl Iterator<Item = (&str, &str)> {
+    output.lines().filter_map(|line| {
+        let line = line
+            .strip_prefix("cargo::")
+            .or_else(|| line.strip_prefix("cargo:"))?;
+        let mut parts = line.splitn(2, '=');
+        Some((parts.next()?, parts.next()?))
+    })
+}
+
+/// Returns the first dependency of a Makefile-style dep-info file.
+fn first_dependency(dep_info: &str) -> Option<String> {
+    let line = dep_info.lines().find(|line| !line.trim().is_empty())?;
+    let deps = &line[line.find(": ")? + 2..];
+
+    let mut result = String::new();
+    let mut chars = deps.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            ' ' => break,
+            c => result.push(c),
+        }
+    }
+
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+fn env_values(fingerprint: &Value) -> BTreeMap<String, Option<String>> {
+    fingerprint["local"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|local| {
+            let env = &local["RerunIfEnvChanged"];
+            let var = env["var"].as_str()?;
+            Some((var.to_owned(), env["val"].as_str().map(str::to_owned)))
+        })
+        .collect()
+}
+
+/// Returns the fingerprint hash of the compiled build script a run depends on,
+/// in the form cargo writes it next to the compiled build script.
+fn build_script_fingerprint(fingerprint: &Value) -> Option<String> {
+    let hash = fingerprint["deps"]
+        .as_array()?
+        .iter()
+        .find(|dep| dep[1] == "build_script_build")?[3]
+        .as_u64()?;
+
+    Some(
+        hash.to_le_bytes()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
+}
+
+/// Strips the trailing `-<hash>` from a build directory name.
+fn package_name(dir: &Path) -> String {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    match name.rfind('-') {
+        Some(index) if name[index + 1..].chars().all(|c| c.is_ascii_hexdigit()) => {
+            name[..index].to_owned()
+        }
+        _ => name,
+    }
+}
+
+/// Finds the outermost directory with a workspace `Cargo.toml`,
+/// or the nearest directory with any `Cargo.toml`.
+fn workspace_root(current_dir: &Path) -> PathBuf {
+    let manifests = current_dir
+        .ancestors()
+        .filter(|dir| dir.join("Cargo.toml").is_file());
+
+    let mut nearest = None;
+    let mut workspace = None;
+    for dir in manifests {
+        nearest.get_or_insert(dir);
+        let is_workspace = fs::read_to_string(dir.join("Cargo.toml"))
+            .is_ok_and(|manifest| manifest.contains("[workspace]"));
+        if is_workspace {
+            workspace = Some(dir);
+        }
+    }
+
+    workspace.or(nearest).unwrap_or(current_dir).to_path_buf()
+}
+
+/// Returns the directory cargo writes `profile` to, built-in profiles share `debug` and `release`.
+fn profile_dir_name(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_script_fingerprint, first_dependency, instructions, package_name, profile_dir_name,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn parses_instructions() {
+        let output = "\
+cargo:rerun-if-changed=web/src
+cargo::rerun-if-env-changed=PROFILE
+not an instruction
+cargo:rustc-cfg=has_web_ui";
+
+        assert_eq!(
+            instructions(output).collect::<Vec<_>>(),
+            vec![
+                ("rerun-if-changed", "web/src"),
+                ("rerun-if-env-changed", "PROFILE"),
+                ("rustc-cfg", "has_web_ui"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_dep_info() {
+        assert_eq!(
+            first_dependency(
+                "/t/build/a-0123/build_script_build-0123.d: my\\ crate/build.rs src/x.rs\n"
+            ),
+            Some("my crate/build.rs".to_owned())
+        );
+        assert_eq!(first_dependency("\n"), None);
+    }
+
+    #[test]
+    fn reads_build_script_fingerprint() {
+        let fingerprint = serde_json::json!({
+            "deps": [[15119899897727489441u64, "build_script_build", false, 972964337188571981u64]],
+        });
+
+        assert_eq!(
+            build_script_fingerprint(&fingerprint),
+            Some("4d8b3fe1e8a9800d".to_owned())
+        );
+        assert_eq!(build_script_fingerprint(&serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn strips_hash_from_package_name() {
+        assert_eq!(
+            package_name(Path::new(
+                "target/debug/build/change-detection-tests-npm-build-544702121a9875d9"
+            )),
+            "change-detection-tests-npm-build"
+        );
+    }
+
+    #[test]
+    fn maps_built_in_profiles_to_their_directories() {
+        assert_eq!(profile_dir_name("dev"), "debug");
+        assert_eq!(profile_dir_name("test"), "debug");
+        assert_eq!(profile_dir_name("bench"), "release");
+        assert_eq!(profile_dir_name("release"), "release");
+        assert_eq!(profile_dir_name("ci"), "ci");
+    }
+}
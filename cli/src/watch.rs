@@ -0,0 +1,258 @@
+use crate::target_dir::Layout;
+use anyhow::Result;
+use change_detection::watch::Watcher;
+use std::{
+    collections::BTreeSet,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+/// Runs cargo with `cargo_args` every time a path tracked by a workspace build script changes.
+///
+/// A watcher is always alive, so changes saved while cargo is running trigger the next run.
+pub fn watch(layout: &Layout, cargo_args: &[OsString], debounce: Duration) -> Result<()> {
+    let mut tracked = Tracked::read(layout)?;
+    let mut watcher = Watcher::new(tracked.paths())?.debounce(debounce);
+
+    loop {
+        run_cargo(cargo_args)?;
+
+        // Start the next watcher before draining the current one, so no change falls in between.
+        let next = Tracked::read(layout)?;
+        let next_watcher = Watcher::new(next.paths())?.debounce(debounce);
+        let mut changed: Vec<PathBuf> = watcher
+            .wait_timeout(Duration::from_millis(0))?
+            .into_iter()
+            .filter(|path| tracked.triggers(path) || next.triggers(path))
+            .collect();
+        tracked = next;
+        watcher = next_watcher;
+
+        if changed.is_empty() {
+            eprintln!("[change-detection] {}", tracked.describe());
+        }
+        while changed.is_empty() {
+            changed = watcher
+                .wait()?
+                .into_iter()
+                .filter(|path| tracked.triggers(path))
+                .collect();
+        }
+        for path in &changed {
+            eprintln!("[change-detection] changed {}", path.display());
+        }
+    }
+}
+
+/// Paths whose changes trigger a cargo run.
+#[derive(Debug, Default)]
+struct Tracked {
+    /// Paths from `rerun-if-changed` instructions, anything inside a directory counts like cargo does.
+    recursive: BTreeSet<PathBuf>,
+    /// Paths recorded by build scripts using change detection, which passed their filters.
+    /// Paths created inside a recorded directory later count as well.
+    exact: BTreeSet<PathBuf>,
+    /// Entries of recorded directories which weren't recorded, rejected by filters or pruned.
+    excluded: BTreeSet<PathBuf>,
+    /// The workspace is watched as a whole while no build script run tracks anything,
+    /// for example before the first successful build.
+    workspace: bool,
+}
+
+impl Tracked {
+    /// Collects paths tracked by the last runs of workspace build scripts.
+    fn read(layout: &Layout) -> Result<Tracked> {
+        let mut result = Tracked::default();
+
+        // The build directory doesn't exist before the first build.
+        let runs = if layout.profile_dir.join("build").is_dir() {
+            layout.build_script_runs()?
+        } else {
+            vec![]
+        };
+        for run in runs {
+            let in_workspace = run
+                .package_root
+                .as_ref()
+                .is_some_and(|root| root.starts_with(&layout.workspace_root));
+            if !in_workspace {
+                continue;
+            }
+
+            match recorded(&run.dir.join("out/change-detection")) {
+                Some(recorded) => result
+                    .exact
+                    .extend(recorded.lines().map(|path| run.resolve(Path::new(path)))),
+                None => result
+                    .recursive
+                    .extend(run.rerun_if_changed.iter().map(|path| run.resolve(path))),
+            }
+        }
+
+        for dir in &result.exact {
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let path = entry?.path();
+                if !result.exact.contains(&path) {
+                    result.excluded.insert(path);
+                }
+            }
+        }
+
+        if result.recursive.is_empty() && result.exact.is_empty() {
+            result.workspace = true;
+            result.recursive = workspace_entries(layout)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Returns paths to watch, recorded paths inside recorded directories are watched with them.
+    fn paths(&self) -> Vec<PathBuf> {
+        let outermost = self.exact.iter().filter(|path| {
+            path.parent()
+                .is_none_or(|parent| !self.exact.contains(parent))
+        });
+
+        self.recursive.iter().chain(outermost).cloned().collect()
+    }
+
+    fn triggers(&self, path: &Path) -> bool {
+        if self
+            .recursive
+            .iter()
+            .any(|tracked| path.starts_with(tracked))
+            || self.exact.contains(path)
+        {
+            return true;
+        }
+
+        !self
+            .excluded
+            .iter()
+            .any(|excluded| path.starts_with(excluded))
+            && path.ancestors().skip(1).any(|dir| self.exact.contains(dir))
+    }
+
+    fn describe(&self) -> String {
+        if self.workspace {
+            "no paths are tracked by workspace build scripts yet, watching the workspace".into()
+        } else {
+            format!("watching {} paths", self.recursive.len() + self.exact.len())
+        }
+    }
+}
+
+/// Reads the paths a build script run recorded in `dir`, one `tracked-*` file per generate call.
+///
+/// Build scripts record only when run with [`RECORD_ENV`] set, those which last ran without it
+/// are watched by their `rerun-if-changed` paths.
+fn recorded(dir: &Path) -> Option<String> {
+    let mut result = None;
+    for entry in fs::read_dir(dir).ok()? {
+        let path = entry.ok()?.path();
+        let is_record = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with("tracked-"));
+        if is_record {
+            let recorded: &mut String = result.get_or_insert_with(String::new);
+            recorded.push_str(&fs::read_to_string(path).ok()?);
+        }
+    }
+
+    result
+}
+
+/// Lists the workspace root without the target directory and hidden entries like `.git`.
+fn workspace_entries(layout: &Layout) -> Result<BTreeSet<PathBuf>> {
+    let mut result = BTreeSet::new();
+    for entry in fs::read_dir(&layout.workspace_root)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if !hidden && !layout.profile_dir.starts_with(&path) {
+            result.insert(path);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Asks build scripts using change detection to record their tracked paths.
+const RECORD_ENV: &str = "CHANGE_DETECTION_RECORD";
+
+fn run_cargo(cargo_args: &[OsString]) -> Result<()> {
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let status = Command::new(cargo)
+        .args(cargo_args)
+        .env(RECORD_ENV, "1")
+        .status()?;
+
+    if !status.success() {
+        eprintln!("[change-detection] cargo failed: {}", status);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recorded, Tracked};
+    use std::{
+        env, fs,
+        path::{Path, PathBuf},
+    };
+
+    #[test]
+    fn recorded_paths_apply_filters() {
+        let tracked = Tracked {
+            recursive: vec![PathBuf::from("/ws/proto")].into_iter().collect(),
+            exact: vec![PathBuf::from("/ws/web"), PathBuf::from("/ws/web/src")]
+                .into_iter()
+                .collect(),
+            excluded: vec![PathBuf::from("/ws/web/dist")].into_iter().collect(),
+            workspace: false,
+        };
+
+        assert_eq!(
+            tracked.paths(),
+            vec![PathBuf::from("/ws/proto"), PathBuf::from("/ws/web")]
+        );
+        assert!(tracked.triggers(Path::new("/ws/proto/a/b.proto")));
+        assert!(tracked.triggers(Path::new("/ws/web/src")));
+        assert!(tracked.triggers(Path::new("/ws/web/src/components/new.js")));
+        assert!(tracked.triggers(Path::new("/ws/web/package.json")));
+        assert!(!tracked.triggers(Path::new("/ws/web/dist")));
+        assert!(!tracked.triggers(Path::new("/ws/web/dist/index.js")));
+        assert!(!tracked.triggers(Path::new("/ws/build.rs")));
+    }
+
+    #[test]
+    fn reads_records_of_every_generate_call() {
+        let dir = env::temp_dir().join(format!(
+            "cargo-change-detection-recorded-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(recorded(&dir), None);
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tracked-1-a"), "web\n").unwrap();
+        fs::write(dir.join("tracked-1-b"), "proto\n").unwrap();
+        fs::write(dir.join("explain.manifest"), "").unwrap();
+
+        let mut paths: Vec<String> = recorded(&dir).unwrap().lines().map(String::from).collect();
+        paths.sort();
+        assert_eq!(paths, ["proto", "web"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
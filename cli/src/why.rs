@@ -0,0 +1,167 @@
+use crate::target_dir::BuildScriptRun;
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// A reason for a build script to rerun on the next build.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reason {
+    /// The build script has never finished a run.
+    NeverRun,
+    /// A tracked path does not exist, cargo reruns the build script every time.
+    Missing(PathBuf),
+    /// A tracked path, or a file inside a tracked directory, is newer than the last run.
+    Modified(PathBuf),
+    /// A tracked environment variable differs from the last run.
+    EnvChanged(String),
+    /// There are no `rerun-if-changed` instructions and a package file is newer than the last run.
+    PackageModified(PathBuf),
+}
+
+/// Finds all reasons for `run` to rerun on the next build.
+pub fn reasons(run: &BuildScriptRun) -> io::Result<Vec<Reason>> {
+    let invoked = match run.invoked {
+        Some(invoked) => invoked,
+        None => return Ok(vec![Reason::NeverRun]),
+    };
+
+    let mut result = vec![];
+
+    for path in &run.rerun_if_changed {
+        let resolved = run.resolve(path);
+        if fs::symlink_metadata(&resolved).is_err() {
+            result.push(Reason::Missing(path.clone()));
+        } else if let Some(newer) = find_newer(&resolved, invoked, &|_| true)? {
+            let relative = newer.strip_prefix(&resolved).unwrap_or(&newer);
+            result.push(Reason::Modified(path.join(relative)));
+        }
+    }
+
+    for var in &run.rerun_if_env_changed {
+        if let Some(recorded) = run.env_values.get(var) {
+            if env::var(var).ok() != *recorded {
+                result.push(Reason::EnvChanged(var.clone()));
+            }
+        }
+    }
+
+    if run.rerun_if_changed.is_empty() {
+        if let Some(root) = &run.package_root {
+            let skip_target = |path: &Path| {
+                path.file_name()
+                    .is_none_or(|name| name != "target" && !name.to_string_lossy().starts_with('.'))
+            };
+            if let Some(newer) = find_newer(root, invoked, &skip_target)? {
+                let relative = newer.strip_prefix(root).unwrap_or(&newer);
+                result.push(Reason::PackageModified(relative.to_path_buf()));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Finds a path inside `path` modified after `reference`, descending into directories
+/// accepted by `descend`.
+pub fn find_newer(
+    path: &Path,
+    reference: SystemTime,
+    descend: &dyn Fn(&Path) -> bool,
+) -> io::Result<Option<PathBuf>> {
+    let metadata = fs::metadata(path)?;
+    if metadata.modified()? > reference {
+        return Ok(Some(path.to_path_buf()));
+    }
+
+    if !metadata.is_dir() {
+        return Ok(None);
+    }
+
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() && !descend(&entry) {
+            continue;
+        }
+        if let Some(newer) = find_newer(&entry, reference, descend)? {
+            return Ok(Some(newer));
+        }
+    }
+
+    Ok(None)
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::NeverRun => write!(f, "the build script has never run"),
+            Reason::Missing(path) => write!(
+                f,
+                "{} does not exist, the build script reruns on every build",
+                path.display()
+            ),
+            Reason::Modified(path) => {
+                write!(f, "{} was modified after the last run", path.display())
+            }
+            Reason::EnvChanged(var) => write!(f, "environment variable {} changed", var),
+            Reason::PackageModified(path) => write!(
+                f,
+                "{} was modified and there are no rerun-if-changed instructions",
+                path.display()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reasons, Reason};
+    use crate::target_dir::BuildScriptRun;
+    use std::{
+        collections::BTreeMap,
+        env, fs,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn detects_missing_and_modified_paths() {
+        let root =
+            env::temp_dir().join(format!("cargo-change-detection-why-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("web/src")).unwrap();
+        fs::write(root.join("web/src/index.js"), "let a = 1;").unwrap();
+
+        let run = |invoked| BuildScriptRun {
+            package: "web".into(),
+            dir: root.join("target/debug/build/web-0123"),
+            package_root: Some(root.clone()),
+            invoked: Some(invoked),
+            rerun_if_changed: vec![PathBuf::from("web/src"), PathBuf::from("web/dist")],
+            rerun_if_env_changed: vec![],
+            env_values: BTreeMap::new(),
+        };
+
+        let future = SystemTime::now() + Duration::from_secs(3600);
+        assert_eq!(
+            reasons(&run(future)).unwrap(),
+            vec![Reason::Missing(PathBuf::from("web/dist"))]
+        );
+
+        let past = SystemTime::now() - Duration::from_secs(3600);
+        assert_eq!(
+            reasons(&run(past)).unwrap(),
+            vec![
+                Reason::Modified(PathBuf::from("web/src")),
+                Reason::Missing(PathBuf::from("web/dist")),
+            ]
+        );
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
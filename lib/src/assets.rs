@@ -0,0 +1,265 @@
+use crate::{format::json_string, hash::hash_file, relative_files, write_if_changed};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Settings of a cache-busting asset manifest.
+///
+/// Every tracked file gets an entry with its path relative to the package root,
+/// a content hash, a hashed file name like `static/app.3f9a1c2b.js`, its size and a MIME type
+/// guessed from the extension.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::{AssetManifest, ChangeDetection};
+/// ChangeDetection::path("static")
+///     .asset_manifest(AssetManifest::new("assets.rs").json("assets.json"))
+///     .generate();
+/// ```
+///
+/// The crate then includes the generated module:
+///
+/// ```no_run
+/// mod assets {
+/// #   pub struct Asset {
+/// #       pub hashed: &'static str,
+/// #   }
+/// #   pub fn get(_path: &str) -> Option<&'static Asset> {
+/// #       None
+/// #   }
+/// #   #[cfg(any())]
+///     include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+/// }
+///
+/// let app = assets::get("static/app.js").unwrap();
+/// println!("<script src=\"/{}\"></script>", app.hashed);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetManifest {
+    rust: PathBuf,
+    json: Option<PathBuf>,
+}
+
+impl AssetManifest {
+    /// Writes a Rust module named `name` into `OUT_DIR`.
+    pub fn new<N: Into<PathBuf>>(name: N) -> AssetManifest {
+        AssetManifest {
+            rust: name.into(),
+            json: None,
+        }
+    }
+
+    /// Writes the same manifest as JSON named `name` into `OUT_DIR` as well.
+    pub fn json<N: Into<PathBuf>>(mut self, name: N) -> AssetManifest {
+        self.json = Some(name.into());
+        self
+    }
+
+    pub(crate) fn write(&self, out_dir: &Path, paths: &[PathBuf]) -> io::Result<()> {
+        let assets = assets(&env::current_dir()?, paths)?;
+
+        write_file(&out_dir.join(&self.rust), &module(&assets))?;
+        if let Some(json) = &self.json {
+            write_file(&out_dir.join(json), &to_json(&assets))?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Asset {
+    path: String,
+    hashed: String,
+    hash: String,
+    size: u64,
+    mime: &'static str,
+}
+
+fn assets(base: &Path, paths: &[PathBuf]) -> io::Result<Vec<Asset>> {
+    relative_files(base, paths)
+        .into_iter()
+        .map(|(path, file)| {
+            let hash = format!("{:016x}", hash_file(&file)?);
+            Ok(Asset {
+                hashed: hashed_name(&path, &hash[..8]),
+                mime: mime(&path),
+                size: fs::metadata(&file)?.len(),
+                path,
+                hash,
+            })
+        })
+        .collect()
+}
+
+/// Inserts `hash` before the extension of the file name, `static/app.js` becomes `static/app.<hash>.js`.
+fn hashed_name(path: &str, hash: &str) -> String {
+    let name_start = path.rfind('/').map_or(0, |index| index + 1);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = name_start + dot;
+            format!("{}.{}{}", &path[..dot], hash, &path[dot..])
+        }
+        _ => format!("{}.{}", path, hash),
+    }
+}
+
+fn mime(path: &str) -> &'static str {
+    let extension = path
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rfind('.').map(|dot| &name[dot + 1..]))
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" | "map" => "application/json",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn module(assets: &[Asset]) -> String {
+    let mut result = String::from(
+        "// Generated by change-detection, do not edit.
+
+/// An entry of the asset manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Asset {
+    /// A path relative to the package root.
+    pub path: &'static str,
+    /// The path with the content hash inserted before the extension.
+    pub hashed: &'static str,
+    /// A content hash.
+    pub hash: &'static str,
+    /// A size in bytes.
+    pub size: u64,
+    /// A MIME type guessed from the extension.
+    pub mime: &'static str,
+}
+
+/// All assets sorted by path.
+pub static ASSETS: &[Asset] = &[
+",
+    );
+    for asset in assets {
+        result.push_str(&format!(
+            "    Asset {{ path: {:?}, hashed: {:?}, hash: {:?}, size: {}, mime: {:?} }},\n",
+            asset.path, asset.hashed, asset.hash, asset.size, asset.mime
+        ));
+    }
+    result.push_str(
+        "];
+
+/// Returns the asset with the given path.
+pub fn get(path: &str) -> Option<&'static Asset> {
+    ASSETS
+        .binary_search_by(|asset| asset.path.cmp(path))
+        .ok()
+        .map(|index| &ASSETS[index])
+}
+",
+    );
+
+    result
+}
+
+fn to_json(assets: &[Asset]) -> String {
+    let entries: Vec<String> = assets
+        .iter()
+        .map(|asset| {
+            format!(
+                "{}:{{\"hashed\":{},\"hash\":{},\"size\":{},\"mime\":{}}}",
+                json_string(&asset.path),
+                json_string(&asset.hashed),
+                json_string(&asset.hash),
+                asset.size,
+                json_string(asset.mime)
+            )
+        })
+        .collect();
+
+    format!("{{{}}}\n", entries.join(","))
+}
+
+fn write_file(path: &Path, content: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    write_if_changed(path, content)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assets, hashed_name, mime, to_json};
+    use crate::test_utils::TempDir;
+    use std::fs;
+
+    #[test]
+    fn hashes_file_names() {
+        assert_eq!(
+            hashed_name("static/app.js", "3f9a1c2b"),
+            "static/app.3f9a1c2b.js"
+        );
+        assert_eq!(
+            hashed_name("static/app.min.js", "3f9a1c2b"),
+            "static/app.min.3f9a1c2b.js"
+        );
+        assert_eq!(
+            hashed_name("static.d/LICENSE", "3f9a1c2b"),
+            "static.d/LICENSE.3f9a1c2b"
+        );
+        assert_eq!(hashed_name(".htaccess", "3f9a1c2b"), ".htaccess.3f9a1c2b");
+    }
+
+    #[test]
+    fn guesses_mime_types() {
+        assert_eq!(mime("static/index.HTML"), "text/html");
+        assert_eq!(mime("static/imgs/01.jpg"), "image/jpeg");
+        assert_eq!(mime("static.d/LICENSE"), "application/octet-stream");
+    }
+
+    #[test]
+    fn writes_json_manifest() {
+        let tmp = TempDir::new("assets");
+        fs::create_dir_all(tmp.path().join("static")).unwrap();
+        fs::write(tmp.path().join("static/app.js"), "let a = 1;").unwrap();
+
+        let assets = assets(
+            tmp.path(),
+            &[tmp.path().join("static"), tmp.path().join("static/app.js")],
+        )
+        .unwrap();
+        let hash = &assets[0].hash;
+
+        assert_eq!(
+            to_json(&assets),
+            format!(
+                "{{\"static/app.js\":{{\"hashed\":\"static/app.{}.js\",\"hash\":\"{}\",\"size\":10,\"mime\":\"text/javascript\"}}}}\n",
+                &hash[..8],
+                hash
+            )
+        );
+    }
+}
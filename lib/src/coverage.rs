@@ -0,0 +1,155 @@
+use crate::{decision::FilterDecision, sink::slash};
+use std::{
+    cell::Cell,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Counts paths matched by an include/exclude filter pair during traversal.
+#[derive(Debug, Default)]
+pub(crate) struct Hits {
+    include: Cell<usize>,
+    exclude: Cell<usize>,
+}
+
+impl Hits {
+    /// Records `decision`, an include filter matched unless it rejected the path.
+    pub(crate) fn record(&self, decision: FilterDecision) {
+        match decision {
+            FilterDecision::Unfiltered | FilterDecision::RejectedByInclude => {}
+            FilterDecision::Accepted => increment(&self.include),
+            FilterDecision::RejectedByExclude => {
+                increment(&self.include);
+                increment(&self.exclude);
+            }
+        }
+    }
+
+    /// Reports filters of `scope` which matched nothing.
+    pub(crate) fn unmatched(
+        &self,
+        scope: &str,
+        has_include: bool,
+        has_exclude: bool,
+        findings: &mut Vec<Finding>,
+    ) {
+        if has_include && self.include.get() == 0 {
+            findings.push(Finding::UnmatchedFilter {
+                filter: "include",
+                scope: scope.to_owned(),
+            });
+        }
+        if has_exclude && self.exclude.get() == 0 {
+            findings.push(Finding::UnmatchedFilter {
+                filter: "exclude",
+                scope: scope.to_owned(),
+            });
+        }
+    }
+}
+
+fn increment(counter: &Cell<usize>) {
+    counter.set(counter.get() + 1);
+}
+
+/// A rule which didn't contribute to the tracked paths, likely gone stale after a rename.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Finding {
+    /// A filter which matched no visited path.
+    UnmatchedFilter { filter: &'static str, scope: String },
+    /// A tracked path which produced no tracked paths at all.
+    EmptyRoot(PathBuf),
+}
+
+impl Finding {
+    /// Reports `root` unless one of the paths tracked under it exists.
+    ///
+    /// Tracking directories alone is fine, cargo scans them recursively.
+    pub(crate) fn empty_root(root: &Path, tracked: &[PathBuf], findings: &mut Vec<Finding>) {
+        if !tracked.iter().any(|path| path.exists()) {
+            findings.push(Finding::EmptyRoot(root.into()));
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UnmatchedFilter { filter, scope } => write!(
+                f,
+                "change-detection: the {} filter of {} matched nothing",
+                filter, scope
+            ),
+            Finding::EmptyRoot(root) => write!(
+                f,
+                "change-detection: path {} produced no tracked paths",
+                slash(root)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finding, Hits};
+    use crate::decision::FilterDecision;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn reports_unmatched_filters() {
+        let hits = Hits::default();
+        hits.record(FilterDecision::RejectedByInclude);
+        hits.record(FilterDecision::Accepted);
+
+        let mut findings = vec![];
+        hits.unmatched("the builder", true, true, &mut findings);
+        assert_eq!(
+            findings,
+            vec![Finding::UnmatchedFilter {
+                filter: "exclude",
+                scope: "the builder".into()
+            }]
+        );
+        assert_eq!(
+            findings[0].to_string(),
+            "change-detection: the exclude filter of the builder matched nothing"
+        );
+
+        hits.record(FilterDecision::RejectedByExclude);
+        findings.clear();
+        hits.unmatched("the builder", true, true, &mut findings);
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn reports_roots_without_tracked_paths() {
+        let mut findings = vec![];
+        Finding::empty_root(Path::new("fixtures-03"), &[], &mut findings);
+        Finding::empty_root(
+            Path::new("fixtures-05"),
+            &[PathBuf::from("fixtures-05")],
+            &mut findings,
+        );
+        Finding::empty_root(
+            Path::new("fixtures-04"),
+            &[
+                PathBuf::from("fixtures-04/dist"),
+                PathBuf::from("fixtures-04/src"),
+            ],
+            &mut findings,
+        );
+        Finding::empty_root(
+            Path::new("fixtures-01"),
+            &[PathBuf::from("fixtures-01"), PathBuf::from("fixtures-01/a")],
+            &mut findings,
+        );
+
+        assert_eq!(
+            findings,
+            vec![
+                Finding::EmptyRoot(PathBuf::from("fixtures-03")),
+                Finding::EmptyRoot(PathBuf::from("fixtures-05"))
+            ]
+        );
+    }
+}
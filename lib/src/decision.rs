@@ -0,0 +1,309 @@
+use crate::{prune::Pruned, sink::slash};
+use path_matchers::PathMatcher;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// Explains whether a path is tracked, returned by
+/// [`ChangeDetectionBuilder::explain`](crate::ChangeDetectionBuilder::explain).
+///
+/// Its `Display` implementation is a single line suitable for a `cargo:warning`.
+///
+/// # Examples:
+///
+/// ```
+/// # use change_detection::ChangeDetection;
+/// let builder = ChangeDetection::path_exclude("src", |path: &std::path::Path| {
+///     path.ends_with("test_utils.rs")
+/// });
+///
+/// let decision = builder.explain("src/test_utils.rs");
+/// assert!(!decision.is_tracked());
+/// assert_eq!(
+///     decision.to_string(),
+///     "change-detection: src/test_utils.rs is not tracked; \
+///      root src: rejected by the exclude filter of the path"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decision {
+    /// The explained path.
+    pub path: PathBuf,
+    /// Whether the path exists, missing paths are never tracked.
+    pub exists: bool,
+    /// Every tracked path covering the explained path, in the order they were added.
+    pub roots: Vec<RootDecision>,
+}
+
+impl Decision {
+    /// Returns `true` if the path is tracked under any root.
+    pub fn is_tracked(&self) -> bool {
+        self.exists && self.roots.iter().any(RootDecision::is_tracked)
+    }
+}
+
+/// The decision for the explained path under one tracked path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootDecision {
+    /// The tracked path covering the explained path.
+    pub root: PathBuf,
+    /// The group the root belongs to.
+    pub group: Option<String>,
+    /// Global include/exclude filters of the builder and enclosing groups, outermost first.
+    pub global: Vec<(String, FilterDecision)>,
+    /// Include/exclude filters given together with the root.
+    pub path: FilterDecision,
+    /// An ancestor of the explained path, or the path itself, which isn't traversed.
+    pub hidden_by: Option<Hidden>,
+}
+
+impl RootDecision {
+    /// Returns `true` if every filter accepts the path and no ancestor hides it.
+    pub fn is_tracked(&self) -> bool {
+        self.hidden_by.is_none()
+            && self.global.iter().all(|(_, decision)| decision.accepts())
+            && self.path.accepts()
+    }
+}
+
+/// Why the traversal doesn't reach the explained path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hidden {
+    /// The ancestor isn't a directory, for example since it doesn't exist.
+    NotADirectory(PathBuf),
+    /// The path is excluded by default, see
+    /// [`without_default_excludes`](crate::ChangeDetectionBuilder::without_default_excludes).
+    Pruned(PathBuf),
+    /// The path is ignored by an ignore file, see `respect_ignore_files`.
+    Ignored(PathBuf),
+}
+
+/// The result of an include/exclude filter pair for the explained path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// There are no filters.
+    Unfiltered,
+    /// The path passed the filters.
+    Accepted,
+    /// The path doesn't match the include filter.
+    RejectedByInclude,
+    /// The path matches the exclude filter.
+    RejectedByExclude,
+}
+
+impl FilterDecision {
+    pub(crate) fn new(
+        include: Option<&dyn PathMatcher>,
+        exclude: Option<&dyn PathMatcher>,
+        path: &Path,
+    ) -> FilterDecision {
+        if include.is_some_and(|filter| !filter.matches(path)) {
+            FilterDecision::RejectedByInclude
+        } else if exclude.is_some_and(|filter| filter.matches(path)) {
+            FilterDecision::RejectedByExclude
+        } else if include.is_none() && exclude.is_none() {
+            FilterDecision::Unfiltered
+        } else {
+            FilterDecision::Accepted
+        }
+    }
+
+    /// Returns `true` unless the path was rejected.
+    pub fn accepts(self) -> bool {
+        self.rejected_by().is_none()
+    }
+
+    /// Returns the kind of the filter which rejected the path.
+    pub(crate) fn rejected_by(self) -> Option<&'static str> {
+        match self {
+            FilterDecision::Unfiltered | FilterDecision::Accepted => None,
+            FilterDecision::RejectedByInclude => Some("include"),
+            FilterDecision::RejectedByExclude => Some("exclude"),
+        }
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = slash(&self.path);
+        if !self.exists {
+            return write!(
+                f,
+                "change-detection: {} is not tracked, it doesn't exist",
+                path
+            );
+        }
+        if self.roots.is_empty() {
+            return write!(
+                f,
+                "change-detection: {} is not tracked, no tracked path covers it",
+                path
+            );
+        }
+
+        write!(
+            f,
+            "change-detection: {} is {}",
+            path,
+            if self.is_tracked() {
+                "tracked"
+            } else {
+                "not tracked"
+            }
+        )?;
+        for root in &self.roots {
+            write!(f, "; {}", root)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for RootDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "root {}", slash(&self.root))?;
+        if let Some(group) = &self.group {
+            write!(f, " in group {}", group)?;
+        }
+
+        match &self.hidden_by {
+            Some(Hidden::NotADirectory(ancestor)) => {
+                return write!(f, ": not traversed, {} isn't a directory", slash(ancestor));
+            }
+            Some(Hidden::Pruned(pruned)) => {
+                return write!(
+                    f,
+                    ": not traversed, {} is excluded by default",
+                    slash(pruned)
+                );
+            }
+            Some(Hidden::Ignored(ignored)) => {
+                return write!(f, ": not traversed, {} is ignored", slash(ignored));
+            }
+            None => {}
+        }
+        for (scope, decision) in &self.global {
+            if let Some(filter) = decision.rejected_by() {
+                return write!(f, ": rejected by the {} filter of {}", filter, scope);
+            }
+        }
+        if let Some(filter) = self.path.rejected_by() {
+            return write!(f, ": rejected by the {} filter of the path", filter);
+        }
+
+        let filtered = self.path != FilterDecision::Unfiltered
+            || self
+                .global
+                .iter()
+                .any(|(_, decision)| *decision != FilterDecision::Unfiltered);
+        if filtered {
+            write!(f, ": accepted by every filter")
+        } else {
+            write!(f, ": accepted, no filters apply")
+        }
+    }
+}
+
+/// Returns why the traversal of `root` doesn't reach `path`.
+pub(crate) fn hidden_by(root: &Path, path: &Path, pruned: &Pruned) -> Option<Hidden> {
+    if let Some(hidden) = pruned.hidden(root, path) {
+        return Some(hidden);
+    }
+
+    not_a_directory(root, path).map(Hidden::NotADirectory)
+}
+
+/// Returns the topmost ancestor of `path` below `root`, including `root`, which isn't a directory.
+fn not_a_directory(root: &Path, path: &Path) -> Option<PathBuf> {
+    let relative = path.strip_prefix(root).ok()?;
+
+    let mut ancestor = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        if !ancestor.is_dir() {
+            return Some(ancestor);
+        }
+        if components.peek().is_none() {
+            break;
+        }
+        ancestor.push(component);
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{not_a_directory, FilterDecision};
+    use crate::ChangeDetection;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn decides_filters() {
+        let include = |path: &Path| path.starts_with("web");
+        let exclude = |path: &Path| path.ends_with("dist");
+
+        assert_eq!(
+            FilterDecision::new(None, None, "web/dist".as_ref()),
+            FilterDecision::Unfiltered
+        );
+        assert_eq!(
+            FilterDecision::new(Some(&include), Some(&exclude), "web/src".as_ref()),
+            FilterDecision::Accepted
+        );
+        assert_eq!(
+            FilterDecision::new(Some(&include), Some(&exclude), "src".as_ref()),
+            FilterDecision::RejectedByInclude
+        );
+        assert_eq!(
+            FilterDecision::new(Some(&include), Some(&exclude), "web/dist".as_ref()),
+            FilterDecision::RejectedByExclude
+        );
+    }
+
+    #[test]
+    fn finds_ancestors_which_are_not_directories() {
+        assert_eq!(not_a_directory("src".as_ref(), "src/lib.rs".as_ref()), None);
+        assert_eq!(
+            not_a_directory("src".as_ref(), "src/lib.rs/nested".as_ref()),
+            Some(PathBuf::from("src/lib.rs"))
+        );
+        assert_eq!(
+            not_a_directory("missing".as_ref(), "missing/lib.rs".as_ref()),
+            Some(PathBuf::from("missing"))
+        );
+    }
+
+    #[test]
+    fn explains_global_and_group_filters() {
+        let builder = ChangeDetection::exclude(|path: &Path| path.ends_with("a"))
+            .path("fixtures-01")
+            .group("web", |g| {
+                g.include(|path: &Path| !path.ends_with("b"))
+                    .path_include("./fixtures-01", |path: &Path| !path.ends_with("c"))
+            });
+
+        let decision = builder.explain("fixtures-01/b");
+        assert!(decision.is_tracked());
+        assert_eq!(
+            decision.to_string(),
+            "change-detection: fixtures-01/b is tracked; \
+             root fixtures-01: accepted by every filter; \
+             root ./fixtures-01 in group web: rejected by the include filter of group web"
+        );
+        assert_eq!(decision.roots[1].path, FilterDecision::Accepted);
+
+        let decision = builder.explain("fixtures-01/a");
+        assert!(!decision.is_tracked());
+        assert_eq!(
+            decision.roots[0].global,
+            vec![("the builder".to_owned(), FilterDecision::RejectedByExclude)]
+        );
+
+        assert_eq!(
+            builder.explain("src/lib.rs").to_string(),
+            "change-detection: src/lib.rs is not tracked, no tracked path covers it"
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use crate::{
+    hash::{hash_file, StableHasher},
+    relative_files,
+};
+use std::{env, io, path::PathBuf};
+
+/// Hashes relative paths and contents of files among `paths` in a stable order.
+///
+/// Paths inside the current directory are hashed relative to it,
+/// so the digest doesn't depend on where the project is checked out.
+pub(crate) fn digest(paths: &[PathBuf]) -> io::Result<String> {
+    let mut hasher = StableHasher::new();
+    for (name, path) in relative_files(&env::current_dir()?, paths) {
+        hasher.write(name.as_bytes());
+        hasher.write(&[0]);
+        hasher.write(&hash_file(&path)?.to_le_bytes());
+    }
+
+    Ok(format!("{:016x}", hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::digest;
+    use crate::test_utils::TempDir;
+    use std::fs;
+
+    #[test]
+    fn digest_depends_on_names_and_contents_only() {
+        let tmp = TempDir::new("digest");
+        let a = tmp.path().join("a.js");
+        let b = tmp.path().join("b.js");
+        fs::write(&a, "let a = 1;").unwrap();
+        fs::write(&b, "let b = 2;").unwrap();
+
+        let first = digest(&[a.clone(), b.clone(), tmp.path().to_path_buf()]).unwrap();
+        assert_eq!(first, digest(&[b.clone(), a.clone()]).unwrap());
+        assert_eq!(first.len(), 16);
+
+        fs::write(&b, "let b = 3;").unwrap();
+        let second = digest(&[a.clone(), b.clone()]).unwrap();
+        assert_ne!(first, second);
+
+        fs::rename(&b, tmp.path().join("c.js")).unwrap();
+        assert_ne!(second, digest(&[a, tmp.path().join("c.js")]).unwrap());
+    }
+}
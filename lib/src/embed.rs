@@ -0,0 +1,76 @@
+use crate::{relative_files, write_if_changed};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Writes a Rust module embedding the files among `paths` to `target`.
+pub(crate) fn write(target: &Path, paths: &[PathBuf]) -> io::Result<()> {
+    let module = module(&env::current_dir()?, paths);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    write_if_changed(target, module)?;
+
+    Ok(())
+}
+
+/// Generates a sorted table of files among `paths` keyed by their paths relative to `base`.
+fn module(base: &Path, paths: &[PathBuf]) -> String {
+    let files = relative_files(base, paths);
+
+    let mut result = String::from(
+        "// Generated by change-detection, do not edit.
+
+/// Embedded files sorted by their paths relative to the package root.
+pub static FILES: &[(&str, &[u8])] = &[
+",
+    );
+    for (name, path) in &files {
+        let path = path.to_str().expect("can't convert path to utf-8 string");
+        result.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", name, path));
+    }
+    result.push_str(
+        "];
+
+/// Returns the content of an embedded file.
+pub fn get(path: &str) -> Option<&'static [u8]> {
+    FILES
+        .binary_search_by(|(name, _)| (*name).cmp(path))
+        .ok()
+        .map(|index| FILES[index].1)
+}
+",
+    );
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::module;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn embeds_sorted_files_only() {
+        let base = std::env::current_dir().unwrap();
+        let module = module(
+            &base,
+            &[
+                PathBuf::from("fixtures-01/b"),
+                PathBuf::from("fixtures-03"),
+                PathBuf::from("fixtures-01/a"),
+                base.join("fixtures-01/a"),
+            ],
+        );
+
+        let a = base.join(Path::new("fixtures-01/a"));
+        let b = base.join(Path::new("fixtures-01/b"));
+        assert!(module.contains(&format!(
+            "&[\n    (\"fixtures-01/a\", include_bytes!({:?})),\n    (\"fixtures-01/b\", include_bytes!({:?})),\n];",
+            a.to_str().unwrap(),
+            b.to_str().unwrap()
+        )));
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{manifest::Manifest, sink::InstructionSink};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Enables reporting of changes which caused a build script rerun.
+///
+/// `1` compares modification times and sizes, `hash` compares file contents as well.
+pub(crate) const EXPLAIN_ENV: &str = "CHANGE_DETECTION_EXPLAIN";
+
+/// Counts calls in this build script run, a build script may generate instructions several times
+/// and each call is compared with the same call of the previous run.
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Reports changes since the previous run as warnings.
+///
+/// Does nothing unless explanations are enabled with [`EXPLAIN_ENV`].
+/// Errors are reported as warnings too, explanations should never break a build.
+pub(crate) fn explain_changes(
+    paths: &[PathBuf],
+    env: &[String],
+    sink: &mut dyn InstructionSink,
+) -> io::Result<()> {
+    let hash = match env::var(EXPLAIN_ENV) {
+        Ok(mode) if mode == "hash" => true,
+        Ok(mode) if !mode.is_empty() && mode != "0" => false,
+        _ => return Ok(()),
+    };
+    let out_dir = match env::var_os("OUT_DIR") {
+        Some(out_dir) => PathBuf::from(out_dir),
+        None => return Ok(()),
+    };
+
+    let call = CALLS.fetch_add(1, Ordering::SeqCst);
+    for line in explanations(&out_dir, call, paths, env, hash) {
+        sink.warning(&line)?;
+    }
+
+    Ok(())
+}
+
+fn explanations(
+    out_dir: &Path,
+    call: usize,
+    paths: &[PathBuf],
+    env: &[String],
+    hash: bool,
+) -> Vec<String> {
+    let name = match call {
+        0 => "explain.manifest".to_owned(),
+        call => format!("explain-{}.manifest", call),
+    };
+    let manifest_path = out_dir.join("change-detection").join(name);
+
+    let result = Manifest::new(paths, env, hash).and_then(|manifest| {
+        let previous = Manifest::read(&manifest_path)?;
+        manifest.write(&manifest_path)?;
+        Ok(previous.map(|previous| manifest.diff(&previous)))
+    });
+
+    match result {
+        Ok(None) => vec!["change-detection: no previous run recorded, nothing to explain".into()],
+        Ok(Some(differences)) if differences.is_empty() => {
+            vec!["change-detection: no tracked file or environment variable changed".into()]
+        }
+        Ok(Some(differences)) => differences
+            .iter()
+            .map(|difference| format!("change-detection: {}", difference))
+            .collect(),
+        Err(err) => vec![format!("change-detection: can't explain changes: {}", err)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::explanations;
+    use crate::test_utils::TempDir;
+    use path_slash::PathExt;
+    use std::fs;
+
+    #[test]
+    fn explains_modified_files() {
+        let tmp = TempDir::new("explain");
+        let file = tmp.path().join("index.js");
+        fs::write(&file, "let a = 1;").unwrap();
+
+        let paths = [file.clone()];
+        assert_eq!(
+            explanations(tmp.path(), 0, &paths, &[], true),
+            vec!["change-detection: no previous run recorded, nothing to explain"]
+        );
+        assert_eq!(
+            explanations(tmp.path(), 0, &paths, &[], true),
+            vec!["change-detection: no tracked file or environment variable changed"]
+        );
+
+        fs::write(&file, "let a = 12;").unwrap();
+        assert_eq!(
+            explanations(tmp.path(), 0, &paths, &[], true),
+            vec![format!(
+                "change-detection: file {} was modified",
+                file.to_slash().unwrap()
+            )]
+        );
+    }
+
+    #[test]
+    fn compares_each_call_with_its_previous_run() {
+        let tmp = TempDir::new("explain-calls");
+        let web = [tmp.path().join("web.js")];
+        let api = [tmp.path().join("api.js")];
+        fs::write(&web[0], "").unwrap();
+        fs::write(&api[0], "").unwrap();
+
+        explanations(tmp.path(), 0, &web, &[], false);
+        explanations(tmp.path(), 1, &api, &[], false);
+
+        assert_eq!(
+            explanations(tmp.path(), 0, &web, &[], false),
+            vec!["change-detection: no tracked file or environment variable changed"]
+        );
+        assert_eq!(
+            explanations(tmp.path(), 1, &api, &[], false),
+            vec!["change-detection: no tracked file or environment variable changed"]
+        );
+    }
+}
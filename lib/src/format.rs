@@ -0,0 +1,239 @@
+use crate::sink::{slash, CargoWriter, InstructionSink};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+/// An output format of change detection instructions.
+///
+/// Used with [`ChangeDetectionBuilder::generate_with`](crate::ChangeDetectionBuilder::generate_with)
+/// to share tracking rules with non-cargo tooling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// `cargo:rerun-if-changed` and `cargo:rerun-if-env-changed` instructions.
+    Cargo,
+    /// A Makefile depfile, `target: dep1 dep2`.
+    ///
+    /// Every dependency also gets an empty rule, so make doesn't fail once it is removed.
+    Make { target: String },
+    /// A Ninja depfile, `target: dep1 dep2`.
+    Ninja { target: String },
+    /// A JSON object with `paths` and `env` arrays.
+    Json,
+}
+
+impl Format {
+    /// A Makefile depfile for `target`.
+    pub fn make<T: Into<String>>(target: T) -> Format {
+        Format::Make {
+            target: target.into(),
+        }
+    }
+
+    /// A Ninja depfile for `target`.
+    pub fn ninja<T: Into<String>>(target: T) -> Format {
+        Format::Ninja {
+            target: target.into(),
+        }
+    }
+
+    /// Returns a sink writing instructions in this format to `writer`.
+    ///
+    /// Depfile and JSON sinks write everything at once in [`InstructionSink::flush`].
+    pub fn sink<'a, W>(&self, writer: W) -> Box<dyn InstructionSink + 'a>
+    where
+        W: Write + 'a,
+    {
+        match self {
+            Format::Cargo => Box::new(CargoWriter(writer)),
+            Format::Make { target } => Box::new(DepfileSink {
+                writer,
+                target: target.clone(),
+                deps: vec![],
+                phony: true,
+            }),
+            Format::Ninja { target } => Box::new(DepfileSink {
+                writer,
+                target: target.clone(),
+                deps: vec![],
+                phony: false,
+            }),
+            Format::Json => Box::new(JsonSink {
+                writer,
+                paths: vec![],
+                env: vec![],
+            }),
+        }
+    }
+}
+
+struct DepfileSink<W> {
+    writer: W,
+    target: String,
+    deps: Vec<String>,
+    phony: bool,
+}
+
+impl<W: Write> InstructionSink for DepfileSink<W> {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        self.deps.push(escape_depfile(&slash(path)));
+        Ok(())
+    }
+
+    fn rerun_if_env_changed(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn warning(&mut self, _message: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        write!(self.writer, "{}:", escape_depfile(&self.target))?;
+        for dep in &self.deps {
+            write!(self.writer, " {}", dep)?;
+        }
+        writeln!(self.writer)?;
+
+        if self.phony {
+            for dep in &self.deps {
+                writeln!(self.writer, "\n{}:", dep)?;
+            }
+        }
+
+        self.writer.flush()
+    }
+}
+
+struct JsonSink<W> {
+    writer: W,
+    paths: Vec<String>,
+    env: Vec<String>,
+}
+
+impl<W: Write> InstructionSink for JsonSink<W> {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        self.paths.push(slash(path));
+        Ok(())
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        self.env.push(name.to_owned());
+        Ok(())
+    }
+
+    fn warning(&mut self, _message: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            "{{\"paths\":{},\"env\":{}}}",
+            json_array(&self.paths),
+            json_array(&self.env)
+        )?;
+        self.writer.flush()
+    }
+}
+
+/// Escapes spaces, `#` and `$` the way make and ninja read them in depfiles.
+fn escape_depfile(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            ' ' => result.push_str("\\ "),
+            '#' => result.push_str("\\#"),
+            '$' => result.push_str("$$"),
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+
+    result
+}
+
+fn json_array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|item| json_string(item)).collect();
+    format!("[{}]", items.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+    use std::path::Path;
+
+    fn emit(format: Format) -> String {
+        let mut output = vec![];
+        {
+            let mut sink = format.sink(&mut output);
+            sink.rerun_if_changed(Path::new("static/my file.css"))
+                .unwrap();
+            sink.rerun_if_changed(Path::new("static/$price#1.txt"))
+                .unwrap();
+            sink.rerun_if_env_changed("PROFILE").unwrap();
+            sink.flush().unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn cargo() {
+        assert_eq!(
+            emit(Format::Cargo),
+            "\
+cargo:rerun-if-changed=static/my file.css
+cargo:rerun-if-changed=static/$price#1.txt
+cargo:rerun-if-env-changed=PROFILE
+"
+        );
+    }
+
+    #[test]
+    fn make_depfile() {
+        assert_eq!(
+            emit(Format::make("out/app bundle.js")),
+            "\
+out/app\\ bundle.js: static/my\\ file.css static/$$price\\#1.txt
+
+static/my\\ file.css:
+
+static/$$price\\#1.txt:
+"
+        );
+    }
+
+    #[test]
+    fn ninja_depfile() {
+        assert_eq!(
+            emit(Format::ninja("bundle.js")),
+            "bundle.js: static/my\\ file.css static/$$price\\#1.txt\n"
+        );
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            emit(Format::Json),
+            "{\"paths\":[\"static/my file.css\",\"static/$price#1.txt\"],\"env\":[\"PROFILE\"]}\n"
+        );
+    }
+}
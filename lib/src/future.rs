@@ -0,0 +1,123 @@
+use crate::sink::slash;
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+/// A tracked path with a modification time after the current time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FutureMtime {
+    pub(crate) path: PathBuf,
+    pub(crate) ahead: Duration,
+}
+
+/// Finds `paths` modified after `now`.
+///
+/// Paths which don't exist are skipped.
+pub(crate) fn future_mtimes(paths: &[PathBuf], now: SystemTime) -> io::Result<Vec<FutureMtime>> {
+    let mut result = vec![];
+    for path in paths {
+        let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if let Ok(ahead) = modified.duration_since(now) {
+            if ahead > Duration::ZERO {
+                result.push(FutureMtime {
+                    path: path.clone(),
+                    ahead,
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+impl FutureMtime {
+    /// Sets the modification time to `now`.
+    ///
+    /// The file isn't opened for writing, so read-only files can be reset too.
+    /// Directories can be reset on Unix only.
+    pub(crate) fn reset(&self, now: SystemTime) -> io::Result<()> {
+        #[cfg(windows)]
+        let file = {
+            use std::os::windows::fs::OpenOptionsExt;
+
+            const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+            fs::OpenOptions::new()
+                .access_mode(FILE_WRITE_ATTRIBUTES)
+                .open(&self.path)?
+        };
+        #[cfg(not(windows))]
+        let file = fs::File::open(&self.path)?;
+
+        file.set_modified(now)
+    }
+
+    /// Describes the path, `reset` is the result of resetting it or `None` if it wasn't reset.
+    pub(crate) fn message(&self, reset: Option<io::Result<()>>) -> String {
+        match reset {
+            Some(Ok(())) => format!(
+                "change-detection: {} was modified {}s in the future, its modification time was reset",
+                slash(&self.path),
+                self.ahead.as_secs()
+            ),
+            Some(Err(err)) => format!(
+                "change-detection: {} was modified {}s in the future and resetting its modification time failed: {}, \
+                 the build script will rerun on every build until then",
+                slash(&self.path),
+                self.ahead.as_secs(),
+                err
+            ),
+            None => format!(
+                "change-detection: {} was modified {}s in the future, \
+                 the build script will rerun on every build until then",
+                slash(&self.path),
+                self.ahead.as_secs()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::future_mtimes;
+    use crate::test_utils::TempDir;
+    use std::{
+        fs::{self, OpenOptions},
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn finds_and_resets_future_mtimes() {
+        let tmp = TempDir::new("future");
+        let future = tmp.path().join("future.js");
+        let past = tmp.path().join("past.js");
+        fs::write(&future, "let a = 1;").unwrap();
+        fs::write(&past, "let a = 1;").unwrap();
+
+        let now = SystemTime::now();
+        OpenOptions::new()
+            .write(true)
+            .open(&future)
+            .unwrap()
+            .set_modified(now + Duration::from_secs(3600))
+            .unwrap();
+
+        let paths = [future.clone(), past, tmp.path().join("missing.js")];
+        let found = future_mtimes(&paths, now).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].path, future);
+        assert!(found[0].ahead > Duration::from_secs(3590));
+
+        let mut permissions = fs::metadata(&future).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&future, permissions).unwrap();
+
+        found[0].reset(now).unwrap();
+        assert!(future_mtimes(&paths, now).unwrap().is_empty());
+    }
+}
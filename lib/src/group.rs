@@ -0,0 +1,157 @@
+use crate::manifest::Manifest;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Change state of named groups.
+///
+/// Returned by [`ChangeDetectionBuilder::generate_groups`](crate::ChangeDetectionBuilder::generate_groups).
+/// A group is dirty if any of its tracked paths or environment variables changed, appeared or disappeared
+/// since its state was last committed with [`Groups::commit`].
+#[derive(Debug)]
+pub struct Groups {
+    dir: PathBuf,
+    groups: Vec<Group>,
+}
+
+#[derive(Debug)]
+struct Group {
+    name: String,
+    manifest: Manifest,
+    dirty: bool,
+}
+
+impl Groups {
+    pub(crate) fn new(
+        out_dir: &Path,
+        groups: Vec<(String, Vec<PathBuf>, Vec<String>)>,
+    ) -> io::Result<Groups> {
+        let dir = out_dir.join("change-detection").join("groups");
+
+        let groups = groups
+            .into_iter()
+            .map(|(name, paths, env)| {
+                let manifest = Manifest::new(&paths, &env, false)?;
+                let previous = Manifest::read(&dir.join(file_name(&name)))?;
+                let dirty = previous.as_ref() != Some(&manifest);
+
+                Ok(Group {
+                    name,
+                    manifest,
+                    dirty,
+                })
+            })
+            .collect::<io::Result<_>>()?;
+
+        Ok(Groups { dir, groups })
+    }
+
+    /// Returns `true` if the group `name` has changed since the last commit,
+    /// or `None` if there is no group with the name `name`.
+    ///
+    /// A group which has never been committed is always dirty.
+    pub fn is_dirty(&self, name: &str) -> Option<bool> {
+        self.get(name).map(|group| group.dirty)
+    }
+
+    /// Returns names of all dirty groups.
+    pub fn dirty(&self) -> impl Iterator<Item = &str> {
+        self.groups
+            .iter()
+            .filter(|group| group.dirty)
+            .map(|group| group.name.as_str())
+    }
+
+    /// Persists the current state of the group `name`.
+    ///
+    /// Call this after the step depending on the group has succeeded,
+    /// so the group is reported as clean on the next run.
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if there is no group with the name `name`.
+    pub fn commit(&self, name: &str) -> io::Result<()> {
+        let group = self.get(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("unknown change detection group '{}'", name),
+            )
+        })?;
+        group.manifest.write(&self.dir.join(file_name(&group.name)))
+    }
+
+    fn get(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+}
+
+/// Turns a group or task name into a file name.
+pub(crate) fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{test_utils::TempDir, ChangeDetection, MemorySink};
+    use std::fs;
+
+    #[test]
+    fn groups_track_changes_independently() {
+        let tmp = TempDir::new("groups");
+        let out_dir = tmp.path().join("out");
+        let web = tmp.path().join("web");
+        let sql = tmp.path().join("sql");
+        fs::create_dir_all(&web).unwrap();
+        fs::create_dir_all(&sql).unwrap();
+        fs::write(web.join("index.js"), "let a = 1;").unwrap();
+        fs::write(sql.join("schema.sql"), "create table a;").unwrap();
+
+        let generate = || {
+            let mut sink = MemorySink::default();
+            let groups = ChangeDetection::path(tmp.path().join("build.rs"))
+                .group("web", |g| g.path(&web))
+                .group("sql", |g| g.path(&sql))
+                .generate_groups_extended(&out_dir, &mut sink)
+                .unwrap();
+            (groups, sink.paths())
+        };
+
+        let (groups, emitted) = generate();
+        assert_eq!(
+            emitted,
+            vec![
+                tmp.path().join("build.rs"),
+                web.clone(),
+                web.join("index.js"),
+                sql.clone(),
+                sql.join("schema.sql"),
+            ]
+        );
+        assert_eq!(groups.is_dirty("web"), Some(true));
+        assert_eq!(groups.is_dirty("sql"), Some(true));
+        assert_eq!(groups.is_dirty("docs"), None);
+        assert_eq!(
+            groups.commit("docs").unwrap_err().kind(),
+            std::io::ErrorKind::NotFound
+        );
+
+        groups.commit("web").unwrap();
+        groups.commit("sql").unwrap();
+
+        let (groups, _) = generate();
+        assert_eq!(groups.dirty().count(), 0);
+
+        fs::write(web.join("index.js"), "let a = 12;").unwrap();
+
+        let (groups, _) = generate();
+        assert_eq!(groups.is_dirty("web"), Some(true));
+        assert_eq!(groups.is_dirty("sql"), Some(false));
+    }
+}
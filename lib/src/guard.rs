@@ -0,0 +1,118 @@
+use crate::{manifest::Manifest, sink::InstructionSink, StdoutSink};
+use std::{io, path::PathBuf};
+
+/// Detects tracked paths modified while a build script runs.
+///
+/// Returned by [`ChangeDetectionBuilder::guard`](crate::ChangeDetectionBuilder::guard).
+/// A build script modifying its own inputs is rerun by cargo on every build.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::ChangeDetection;
+/// let guard = ChangeDetection::path("web").guard();
+///
+/// // run npm
+///
+/// guard.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Guard {
+    tracked: Vec<PathBuf>,
+    before: Manifest,
+    deny: bool,
+}
+
+impl Guard {
+    pub(crate) fn new(tracked: Vec<PathBuf>) -> io::Result<Guard> {
+        Ok(Guard {
+            before: Manifest::new(&tracked, &[], false)?,
+            tracked,
+            deny: false,
+        })
+    }
+
+    /// Makes [`Guard::finish`] fail instead of warning if tracked paths were modified.
+    pub fn deny(mut self) -> Guard {
+        self.deny = true;
+        self
+    }
+
+    /// Reports tracked paths modified since the guard was created with `cargo:warning`.
+    pub fn finish(self) -> io::Result<()> {
+        self.finish_into(StdoutSink)
+    }
+
+    /// Reports tracked paths modified since the guard was created as warnings to `sink`.
+    pub fn finish_into<S>(self, mut sink: S) -> io::Result<()>
+    where
+        S: InstructionSink,
+    {
+        let messages: Vec<String> = self
+            .modified()?
+            .iter()
+            .map(|path| {
+                format!(
+                    "change-detection: {} during the build script run, it will rerun on every build",
+                    path
+                )
+            })
+            .collect();
+
+        if self.deny && !messages.is_empty() {
+            return Err(io::Error::other(messages.join("\n")));
+        }
+        for message in &messages {
+            sink.warning(message)?;
+        }
+
+        sink.flush()
+    }
+
+    fn modified(&self) -> io::Result<Vec<String>> {
+        let after = Manifest::new(&self.tracked, &[], false)?;
+
+        Ok(after
+            .diff(&self.before)
+            .iter()
+            .map(ToString::to_string)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Guard;
+    use crate::{test_utils::TempDir, Instruction, MemorySink};
+    use path_slash::PathExt;
+    use std::{fs, thread, time::Duration};
+
+    #[test]
+    fn reports_tracked_files_modified_during_run() {
+        let tmp = TempDir::new("guard");
+        let lock = tmp.path().join("package-lock.json");
+        let package = tmp.path().join("package.json");
+        fs::write(&lock, "{}").unwrap();
+        fs::write(&package, "{}").unwrap();
+
+        let guard = Guard::new(vec![lock.clone(), package]).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        fs::write(&lock, r#"{"version":"0.1.0"}"#).unwrap();
+
+        let mut sink = MemorySink::default();
+        guard.finish_into(&mut sink).unwrap();
+        assert_eq!(
+            sink.instructions,
+            vec![Instruction::Warning(format!(
+                "change-detection: file {} was modified during the build script run, \
+                 it will rerun on every build",
+                lock.to_slash().unwrap()
+            ))]
+        );
+
+        let guard = Guard::new(vec![lock.clone()]).unwrap().deny();
+        thread::sleep(Duration::from_millis(10));
+        fs::remove_file(&lock).unwrap();
+        assert!(guard.finish_into(MemorySink::default()).is_err());
+    }
+}
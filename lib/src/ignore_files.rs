@@ -0,0 +1,242 @@
+use crate::logging;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Ignore files read by [`respect_ignore_files`](crate::ChangeDetectionBuilder::respect_ignore_files).
+pub(crate) const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// The default name of ignore files which only affect change detection.
+pub(crate) const CHANGE_IGNORE: &str = ".changeignore";
+
+/// Ignore files consulted while walking tracked directories.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct IgnoreFiles {
+    /// Names of ignore files read in every directory, later ones take precedence.
+    pub(crate) names: Vec<String>,
+    /// Whether `.git/info/exclude` of the enclosing repository is read.
+    pub(crate) git_exclude: bool,
+}
+
+impl IgnoreFiles {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.names.is_empty() && !self.git_exclude
+    }
+}
+
+/// Ignore files in effect while walking below one tracked path.
+///
+/// Ignore files in directories above the tracked path are read up to the repository root,
+/// the directory containing `.git`. Matching happens on canonical paths, so patterns
+/// anchored to a directory work regardless of the form the tracked path was given in.
+#[derive(Debug)]
+pub(crate) struct IgnoreStack {
+    names: Vec<String>,
+    root: PathBuf,
+    canonical: PathBuf,
+    /// Matchers of the entered directories, innermost last.
+    matchers: Vec<Gitignore>,
+    /// Every ignore file read, in the form the traversal reaches it.
+    read: Vec<PathBuf>,
+}
+
+impl IgnoreStack {
+    pub(crate) fn new(files: &IgnoreFiles, root: &Path) -> IgnoreStack {
+        let canonical = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let mut stack = IgnoreStack {
+            names: files.names.clone(),
+            root: root.to_path_buf(),
+            canonical,
+            matchers: vec![],
+            read: vec![],
+        };
+
+        let repository = stack
+            .canonical
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .map(Path::to_path_buf);
+        if let Some(repository) = repository {
+            if files.git_exclude {
+                let exclude = repository.join(".git").join("info").join("exclude");
+                stack.push(&repository, &[(exclude.clone(), exclude)]);
+            }
+
+            let mut parents: Vec<PathBuf> = stack
+                .canonical
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(&repository))
+                .map(Path::to_path_buf)
+                .collect();
+            parents.reverse();
+            for parent in parents {
+                stack.enter_as(&parent, &parent);
+            }
+        }
+
+        stack
+    }
+
+    /// Reads ignore files of `dir`, a directory reached by the traversal.
+    pub(crate) fn enter(&mut self, dir: &Path) {
+        let canonical = self.canonical(dir);
+        self.enter_as(dir, &canonical);
+    }
+
+    /// Forgets ignore files of the last entered directory.
+    pub(crate) fn leave(&mut self) {
+        self.matchers.pop();
+    }
+
+    /// Returns `true` if the innermost ignore file with a matching pattern ignores `path`.
+    pub(crate) fn ignores(&self, path: &Path, is_dir: bool) -> bool {
+        let canonical = self.canonical(path);
+        for matcher in self.matchers.iter().rev() {
+            let matched = matcher.matched(&canonical, is_dir);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// Returns every ignore file read so far.
+    pub(crate) fn read(&self) -> &[PathBuf] {
+        &self.read
+    }
+
+    fn enter_as(&mut self, dir: &Path, canonical: &Path) {
+        let files: Vec<(PathBuf, PathBuf)> = self
+            .names
+            .iter()
+            .map(|name| (canonical.join(name), dir.join(name)))
+            .collect();
+        self.push(canonical, &files);
+    }
+
+    /// Pushes a matcher of `dir` built from `files`, pairs of canonical and reported paths.
+    fn push(&mut self, dir: &Path, files: &[(PathBuf, PathBuf)]) {
+        let mut builder = GitignoreBuilder::new(dir);
+        for (file, reported) in files.iter().filter(|(file, _)| file.is_file()) {
+            if let Some(err) = builder.add(file) {
+                logging::debug(|| format!("error reading {}: {}", file.display(), err));
+            }
+            self.read.push(reported.clone());
+        }
+
+        let matcher = builder.build().unwrap_or_else(|err| {
+            logging::debug(|| format!("error reading ignore files in {}: {}", dir.display(), err));
+            Gitignore::empty()
+        });
+        self.matchers.push(matcher);
+    }
+
+    /// Converts `path`, in the form the traversal reaches it, into a canonical path.
+    fn canonical(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => self.canonical.join(relative),
+            _ => self.canonical.clone(),
+        }
+    }
+}
+
+/// Returns the topmost ancestor of `path` below `root`, including `path` itself,
+/// which ignore files hide from the traversal.
+pub(crate) fn ignored_ancestor(files: &IgnoreFiles, root: &Path, path: &Path) -> Option<PathBuf> {
+    if files.is_empty() {
+        return None;
+    }
+    let relative = path.strip_prefix(root).ok()?;
+
+    let mut stack = IgnoreStack::new(files, root);
+    let mut ancestor = root.to_path_buf();
+    for component in relative.components() {
+        if !ancestor.is_dir() {
+            return None;
+        }
+        stack.enter(&ancestor);
+
+        ancestor.push(component);
+        if stack.ignores(&ancestor, ancestor.is_dir()) {
+            return Some(ancestor);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ignored_ancestor, IgnoreFiles, IgnoreStack, IGNORE_FILES};
+    use crate::test_utils::TempDir;
+    use std::{fs, path::Path};
+
+    fn ignore_files() -> IgnoreFiles {
+        IgnoreFiles {
+            names: IGNORE_FILES.iter().map(|name| name.to_string()).collect(),
+            git_exclude: true,
+        }
+    }
+
+    #[test]
+    fn applies_gitignore_semantics() {
+        let tmp = TempDir::new("ignore-files");
+        let root = tmp.path();
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::create_dir_all(root.join("static/build")).unwrap();
+        fs::create_dir_all(root.join("static/img/build")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "*.swp\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n/static/build/\n").unwrap();
+        fs::write(root.join("static/.ignore"), "!keep.log\n").unwrap();
+
+        let mut stack = IgnoreStack::new(&ignore_files(), &root.join("static"));
+        stack.enter(&root.join("static"));
+
+        assert!(stack.ignores(&root.join("static/app.log"), false));
+        assert!(stack.ignores(&root.join("static/app.js.swp"), false));
+        assert!(!stack.ignores(&root.join("static/keep.log"), false));
+        assert!(!stack.ignores(&root.join("static/app.js"), false));
+        assert!(stack.ignores(&root.join("static/build"), true));
+        assert!(!stack.ignores(&root.join("static/img/build"), true));
+
+        // Directory-only patterns don't match files.
+        fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+        let mut stack = IgnoreStack::new(&ignore_files(), root);
+        stack.enter(root);
+        assert!(stack.ignores(&root.join("dist"), true));
+        assert!(!stack.ignores(&root.join("dist"), false));
+    }
+
+    #[test]
+    fn finds_ignored_ancestors() {
+        let tmp = TempDir::new("ignore-files-ancestor");
+        let root = tmp.path();
+        fs::create_dir_all(root.join("static/build")).unwrap();
+        fs::write(root.join("static/.gitignore"), "build\n").unwrap();
+        fs::write(root.join("static/build/app.js"), "").unwrap();
+
+        assert_eq!(
+            ignored_ancestor(&ignore_files(), root, &root.join("static/build/app.js")),
+            Some(root.join("static/build"))
+        );
+        assert_eq!(
+            ignored_ancestor(
+                &IgnoreFiles::default(),
+                root,
+                &root.join("static/build/app.js")
+            ),
+            None
+        );
+        assert_eq!(
+            ignored_ancestor(&ignore_files(), root, Path::new("elsewhere")),
+            None
+        );
+    }
+}
@@ -77,17 +77,362 @@ fn main() {
 }
 ```
 
+Paths can be split into named groups, each with its own change tracking.
+Group fingerprints are persisted in `OUT_DIR`, so independent steps run only when their own inputs changed:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    let groups = ChangeDetection::path("build.rs")
+        .group("web", |g| g.path("web/src"))
+        .group("proto", |g| g.path("proto"))
+        .generate_groups();
+
+    if groups.is_dirty("web") == Some(true) {
+        // build the web bundle
+        groups.commit("web").unwrap();
+    }
+}
+```
+
+`digest_env` exports a digest of relative paths and contents of all tracked files,
+so the crate can tell asset versions apart with `env!`:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path("static")
+        .digest_env("ASSETS_DIGEST")
+        .generate();
+}
+```
+
+`cfg_if_exists` enables a cfg when a path exists and reruns the build script once it is created or deleted:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path("build.rs")
+        .cfg_if_exists("has_web_ui", "web/dist")
+        .generate();
+}
+```
+
+`embed` generates a module in `OUT_DIR` with an `include_bytes!` table of exactly the tracked files,
+so the embedded assets never drift apart from the tracking instructions:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path("static")
+        .embed("assets.rs")
+        .generate();
+}
+```
+
+The crate includes it with `include!(concat!(env!("OUT_DIR"), "/assets.rs"))` and looks files up
+with `get("static/index.html")`.
+
+`asset_manifest` writes a cache-busting manifest of the tracked files into `OUT_DIR`, as a Rust module
+and optionally as JSON. Each entry holds the path, a content hash, a hashed file name like
+`static/app.3f9a1c2b.js`, the size and a MIME type guessed from the extension:
+
+```rust,no_run
+use change_detection::{AssetManifest, ChangeDetection};
+
+fn main() {
+    ChangeDetection::path("static")
+        .asset_manifest(AssetManifest::new("assets.rs").json("assets.json"))
+        .generate();
+}
+```
+
+Build scripts writing files should use `write_if_changed`, which keeps the modification time
+of files whose contents didn't change, so they don't trigger rebuild loops:
+
+```rust,no_run
+use change_detection::write_if_changed;
+
+fn main() {
+    write_if_changed("web/package-lock.json", r#"{"version":"0.1.0"}"#).unwrap();
+}
+```
+
+Paths written by the build script can be declared with `output`. Outputs overlapping tracked inputs
+make the build script rerun after every build, so they are reported with a warning suggesting
+the exclude rule that fixes it, or fail the build with `deny_overlapping_outputs`:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path("web/src")
+        .output("web/dist")
+        .deny_overlapping_outputs()
+        .generate();
+}
+```
+
+A build script modifying its own inputs is rerun by cargo on every build. `guard` records tracked paths
+before the build script does its work, and `Guard::finish` warns about, or with `deny` fails on,
+every tracked path modified in the meantime:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    let guard = ChangeDetection::path("web/src").guard();
+
+    // run npm
+
+    guard.deny().finish().unwrap();
+}
+```
+
+Tracked files with modification times in the future, left by clock skew on network file systems
+or extracted archives, are reported with a `cargo:warning`, since cargo reruns the build script
+until that time has passed. `reset_future_mtimes` resets them to the current time instead.
+
+External build steps can be wrapped into a `Task`. Its inputs are the tracked paths, its outputs are
+declared with `output`, and the command runs only when an output is missing, older than an input, or
+input contents changed since the last successful run. If the command fails, its output is reported
+with `cargo:warning` and the command runs again on the next build:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+use std::process::Command;
+
+fn main() {
+    let mut npm = Command::new("npm");
+    npm.args(["run", "build"]).current_dir("web");
+
+    ChangeDetection::path("web/src")
+        .output("web/dist")
+        .task("web", npm)
+        .run()
+        .unwrap();
+}
+```
+
+Build scripts of several crates writing into the same directory can serialize their work with
+`PathLock`, an advisory lock keyed by a path. `Task::lock` holds it on every output, so the later
+build script finds the outputs up to date:
+
+```rust,no_run
+use change_detection::PathLock;
+
+fn main() {
+    let _lock = PathLock::acquire("web/dist").unwrap();
+
+    // build web/dist
+}
+```
+
+Filters which matched nothing and tracked paths which produced no tracked paths usually went stale after
+a rename. They are reported with a `cargo:warning`, or fail the build with `deny_unmatched_filters`:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path_exclude("web/src", |path: &std::path::Path| path.ends_with("node_modules"))
+        .deny_unmatched_filters()
+        .generate();
+}
+```
+
+The target directory, the build script's `OUT_DIR` and version control metadata (`.git`, `.hg`, `.svn`, `.jj`)
+are excluded by default and never walked. Cargo scans tracked directories recursively, so a directory containing
+an excluded one, like `.`, isn't tracked itself, only its other entries are, and files added directly inside it
+don't rerun the build script. The target directory is taken from `CARGO_TARGET_DIR` or detected from `OUT_DIR`.
+Paths given explicitly, like `.git/HEAD`, are still tracked, and `without_default_excludes` turns the default
+excludes off.
+
+With the `ignore-files` feature, `respect_ignore_files` skips paths ignored by `.gitignore` and `.ignore` files
+and `.git/info/exclude`, so generated files don't need their patterns repeated in `exclude`. Ignore files are
+read with full gitignore semantics, including those in parent directories up to the repository root.
+Like with the default excludes, a directory containing ignored entries isn't tracked itself:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    #[cfg(feature = "ignore-files")]
+    ChangeDetection::path("static")
+        .respect_ignore_files()
+        .generate();
+}
+```
+
+When what should be tracked differs from what git ignores, like a committed `web/dist`, `change_ignore` reads
+`.changeignore` files with the same syntax, where nested files override their parents. `ignore_file` takes
+another name. Every ignore file read is tracked as well, so editing one reruns the build script. With `dist/`
+in `web/.changeignore`, `web` itself isn't tracked, since cargo would scan `web/dist` with it, only its other
+entries are:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    #[cfg(feature = "ignore-files")]
+    ChangeDetection::path("web")
+        .change_ignore()
+        .generate();
+}
+```
+
+Tracking a directory containing `node_modules` or `target` makes cargo check hundreds of thousands of paths
+on every build. `max_paths` and `max_scan_time` warn with the largest directories once a limit is exceeded,
+and `fallback_to_roots` stops scanning and emits the given paths alone then, which cargo scans recursively
+without the filters. A given path containing the target directory or version control metadata, like `.`,
+is replaced by its other entries:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    ChangeDetection::path("web")
+        .max_paths(10_000)
+        .max_scan_time(std::time::Duration::from_secs(1))
+        .fallback_to_roots()
+        .generate();
+}
+```
+
+To find out why a build script was rerun, set the `CHANGE_DETECTION_EXPLAIN` environment variable to `1`
+(or `hash` to compare file contents too). Every run then records tracked paths and environment variables
+in `OUT_DIR` and emits a `cargo:warning` for each of them which changed since the previous run:
+
+```bash
+CHANGE_DETECTION_EXPLAIN=1 cargo build
+```
+
+To find out why a single path is or isn't tracked, `explain` returns a decision trace listing
+the tracked paths covering it and the result of every include/exclude filter:
+
+```rust,no_run
+use change_detection::ChangeDetection;
+
+fn main() {
+    let builder = ChangeDetection::path("web/src");
+    println!("cargo:warning={}", builder.explain("web/src/foo.js"));
+    builder.generate();
+}
+```
+
+To see which paths were visited and which filter rejected them, set `CHANGE_DETECTION_LOG` to `debug`
+(roots and totals) or `trace` (every visited path). The log goes to stderr, which cargo shows with `-vv`,
+or to a file in `OUT_DIR` named by `CHANGE_DETECTION_LOG_FILE`. With the `log` feature the same messages
+are passed to the `log` crate under the `change_detection` target as well. Like the explanation,
+logging is read at run time only, so touch `build.rs` to rerun the build script:
+
+```bash
+CHANGE_DETECTION_LOG=trace cargo build -vv
+```
+
+The same rules can be written for other build tools with `generate_with`, which supports
+Makefile and Ninja depfiles as well as JSON:
+
+```rust,no_run
+use change_detection::{ChangeDetection, Format};
+
+fn main() {
+    let depfile = std::fs::File::create("web.d").unwrap();
+
+    ChangeDetection::path("web/src")
+        .generate_with(Format::make("web/dist/index.js"), depfile)
+        .unwrap();
+}
+```
+
+Instructions go through the `InstructionSink` trait. `generate_into` accepts any implementation,
+including the bundled `StdoutSink`, `FileSink` and `MemorySink`, which is handy in tests.
+
 You can find generated result with this command:
 
 ```bash
 find . -name output | xargs cat
 ```
 
+The `cargo-change-detection` subcommand reads the same files and reports build scripts
+which will rerun on the next build, together with the paths and environment variables causing it.
+It works offline on an existing target directory:
+
+```bash
+cargo install cargo-change-detection
+cargo change-detection why --release
+```
+
+`cargo change-detection audit` reports build scripts without `rerun-if-changed` instructions,
+tracking missing paths, huge directories like `node_modules`, or paths outside of their package.
+Use `--json` for machine-readable output and `--deny` to fail CI on findings.
+
+`cargo change-detection watch -- <cargo args>` runs cargo every time a path tracked by a workspace
+build script changes. Paths excluded by the tracking rules, like build outputs, never trigger it:
+
+```bash
+cargo change-detection watch -- run --release
+```
+
+`watch` runs cargo with `CHANGE_DETECTION_RECORD` set, so build scripts using this crate record the paths
+passing their filters in `OUT_DIR`. Other build scripts, and those which last ran outside of `watch`,
+are watched by their `rerun-if-changed` paths. Before any build script ran, the whole workspace is watched,
+and changes saved while cargo is running trigger the next run.
+
+The same is available as a library API with the `watch` feature, see `ChangeDetectionBuilder::watch`.
+
 */
+// The examples show whole build scripts, `fn main` included.
+#![allow(clippy::needless_doctest_main)]
+
 use ::path_matchers::PathMatcher;
-use path_slash::PathExt;
+use coverage::{Finding, Hits};
+use limits::{Budget, Limits};
+use prune::{Prune, Pruned};
+use sink::slash;
 use std::path::{Path, PathBuf};
 
+mod assets;
+mod coverage;
+mod decision;
+mod digest;
+mod embed;
+mod explain;
+mod format;
+mod future;
+mod group;
+mod guard;
+mod hash;
+#[cfg(feature = "ignore-files")]
+mod ignore_files;
+mod limits;
+mod lock;
+mod logging;
+mod manifest;
+mod output;
+mod prune;
+mod sink;
+mod task;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "watch")]
+pub mod watch;
+mod write;
+
+pub use assets::AssetManifest;
+pub use decision::{Decision, FilterDecision, Hidden, RootDecision};
+pub use format::Format;
+pub use group::Groups;
+pub use guard::Guard;
+pub use lock::PathLock;
+pub use sink::{FileSink, Instruction, InstructionSink, MemorySink, StdoutSink};
+pub use task::Task;
+pub use write::{write_if_changed, write_with_if_changed};
+
 /// Reexport `path-matchers`.
 pub mod path_matchers {
     pub use ::path_matchers::*;
@@ -109,7 +454,7 @@ pub mod path_matchers {
 ///
 /// This is the same as just write:
 ///
-/// ```ignore
+/// ```
 /// fn main() {
 ///     println!("cargo:rerun-if-changed=src/hello.c");
 /// }
@@ -335,6 +680,21 @@ pub struct ChangeDetectionBuilder {
     include: Option<Box<dyn PathMatcher>>,
     exclude: Option<Box<dyn PathMatcher>>,
     paths: Vec<ChangeDetectionPath>,
+    groups: Vec<(String, ChangeDetectionBuilder)>,
+    env: Vec<String>,
+    digest_env: Option<String>,
+    cfgs: Vec<(String, PathBuf)>,
+    embed: Option<PathBuf>,
+    asset_manifest: Option<AssetManifest>,
+    outputs: Vec<PathBuf>,
+    deny_overlapping_outputs: bool,
+    deny_unmatched_filters: bool,
+    limits: Limits,
+    reset_future_mtimes: bool,
+    no_default_excludes: bool,
+    #[cfg(feature = "ignore-files")]
+    ignore_files: ignore_files::IgnoreFiles,
+    name: Option<String>,
 }
 
 impl ChangeDetectionBuilder {
@@ -481,134 +841,1211 @@ impl ChangeDetectionBuilder {
         self
     }
 
-    pub fn generate(self) {
-        self.generate_extended(print_change_detection_instruction)
-    }
-
-    fn generate_extended<F>(self, mut f: F)
+    /// Declares a named group of paths with independent change tracking.
+    ///
+    /// Instructions are generated for the paths of every group as usual.
+    /// Additionally [`generate_groups`](ChangeDetectionBuilder::generate_groups) reports
+    /// which groups have changed since their state was last committed.
+    ///
+    /// Global filters of this builder are applied to the group paths as well.
+    ///
+    /// # Examples:
+    ///
+    /// To track the web bundle and the protobuf definitions separately:
+    ///
+    /// ```
+    /// # use change_detection::ChangeDetectionBuilder;
+    /// # let builder = ChangeDetectionBuilder::default();
+    /// builder
+    ///     .group("web", |g| g.path("web/src"))
+    ///     .group("proto", |g| g.path("proto"))
+    ///     .generate();
+    /// ```
+    pub fn group<N, F>(mut self, name: N, group: F) -> ChangeDetectionBuilder
     where
-        F: FnMut(&Path),
+        N: Into<String>,
+        F: FnOnce(ChangeDetectionBuilder) -> ChangeDetectionBuilder,
     {
-        for path in &self.paths {
-            path.generate(&self, &mut f);
-        }
+        let name = name.into();
+        let group = group(ChangeDetectionBuilder {
+            name: Some(name.clone()),
+            ..ChangeDetectionBuilder::default()
+        });
+        self.groups.push((name, group));
+        self
     }
 
-    fn filter_include_exclude(&self, path: &Path) -> bool {
-        self.include
-            .as_ref()
-            .map_or(true, |filter| filter.matches(path))
-            && self
-                .exclude
-                .as_ref()
-                .map_or(true, |filter| !filter.matches(path))
+    /// Collects change detection instructions from an environment variable `name`.
+    ///
+    /// # Examples:
+    ///
+    /// To rerun the build script when the `PROFILE` or `WEB_API_URL` variables change:
+    ///
+    /// ```
+    /// # use change_detection::ChangeDetectionBuilder;
+    /// # let builder = ChangeDetectionBuilder::default();
+    /// builder
+    ///     .path("static")
+    ///     .env("PROFILE")
+    ///     .env("WEB_API_URL")
+    ///     .generate();
+    /// ```
+    pub fn env<N>(mut self, name: N) -> ChangeDetectionBuilder
+    where
+        N: Into<String>,
+    {
+        self.env.push(name.into());
+        self
     }
-}
-
-pub enum ChangeDetectionPath {
-    Path(PathBuf),
-    PathInclude(PathBuf, Box<dyn PathMatcher>),
-    PathExclude(PathBuf, Box<dyn PathMatcher>),
-    PathIncludeExclude {
-        path: PathBuf,
-        include: Box<dyn PathMatcher>,
-        exclude: Box<dyn PathMatcher>,
-    },
-}
-
-fn print_change_detection_instruction(path: &Path) {
-    println!(
-        "cargo:rerun-if-changed={}",
-        path.to_slash().expect("can't convert path to utf-8 string")
-    );
-}
-
-impl ChangeDetectionPath {
-    fn collect(&self, builder: &ChangeDetectionBuilder) -> std::io::Result<Vec<PathBuf>> {
-        let filter_fn: Box<dyn Fn(&_) -> bool> =
-            Box::new(|path: &std::path::Path| builder.filter_include_exclude(path));
-
-        let (path, filter): (&PathBuf, Box<dyn Fn(&_) -> bool>) = match self {
-            ChangeDetectionPath::Path(path) => (path, filter_fn),
-            ChangeDetectionPath::PathInclude(path, include_filter) => (
-                path,
-                Box::new(move |p: &Path| filter_fn(p.as_ref()) && include_filter.matches(p)),
-            ),
-            ChangeDetectionPath::PathExclude(path, exclude_filter) => (
-                path,
-                Box::new(move |p: &Path| filter_fn(p.as_ref()) && !exclude_filter.matches(p)),
-            ),
-            ChangeDetectionPath::PathIncludeExclude {
-                path,
-                include,
-                exclude,
-            } => (
-                path,
-                Box::new(move |p: &Path| {
-                    filter_fn(p.as_ref()) && include.matches(p) && !exclude.matches(p)
-                }),
-            ),
-        };
 
-        collect_resources(path, &filter)
+    /// Exports a digest of all tracked files as the compile-time environment variable `name`.
+    ///
+    /// The digest covers relative paths and contents of files passing the filters,
+    /// including files of groups, so it changes whenever any tracked file does.
+    ///
+    /// # Examples:
+    ///
+    /// To tell asset versions apart in production:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// ChangeDetection::path("static")
+    ///     .digest_env("ASSETS_DIGEST")
+    ///     .generate();
+    /// ```
+    ///
+    /// The crate can then read it with `env!("ASSETS_DIGEST")`.
+    pub fn digest_env<N>(mut self, name: N) -> ChangeDetectionBuilder
+    where
+        N: Into<String>,
+    {
+        self.digest_env = Some(name.into());
+        self
     }
 
-    fn generate<F>(&self, builder: &ChangeDetectionBuilder, printer: &mut F)
+    /// Enables the cfg `name` if `path` exists.
+    ///
+    /// `path` is tracked while it exists and its nearest existing ancestor while it doesn't,
+    /// so the build script reruns when `path` is created or deleted. If that would track
+    /// the package root or a directory containing the target directory, a warning is shown
+    /// instead, create the parent directory of `path` to have it tracked. The cfg is declared with `rustc-check-cfg`,
+    /// so it doesn't trigger `unexpected_cfgs` warnings.
+    ///
+    /// # Examples:
+    ///
+    /// To compile the web UI only once it was built:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// ChangeDetection::path("build.rs")
+    ///     .cfg_if_exists("has_web_ui", "web/dist")
+    ///     .generate();
+    /// ```
+    ///
+    /// The crate then uses `#[cfg(has_web_ui)]`.
+    pub fn cfg_if_exists<N, P>(mut self, name: N, path: P) -> ChangeDetectionBuilder
     where
-        F: FnMut(&Path),
+        N: Into<String>,
+        P: Into<PathBuf>,
     {
-        for path in self.collect(builder).expect("error collecting resources") {
-            printer(path.as_ref());
-        }
+        self.cfgs.push((name.into(), path.into()));
+        self
     }
-}
 
-impl<T> From<T> for ChangeDetectionPath
-where
-    T: AsRef<Path>,
-{
-    fn from(path: T) -> Self {
-        ChangeDetectionPath::Path(path.as_ref().into())
+    /// Generates a Rust module named `name` in `OUT_DIR`, embedding all tracked files.
+    ///
+    /// The module contains a `FILES` slice of paths relative to the package root and
+    /// `include_bytes!` contents, sorted by path, and a `get` function looking files up.
+    /// Only files passing the filters are embedded, exactly like they are tracked.
+    ///
+    /// # Examples:
+    ///
+    /// In `build.rs`:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// ChangeDetection::path("static")
+    ///     .embed("assets.rs")
+    ///     .generate();
+    /// ```
+    ///
+    /// In the crate:
+    ///
+    /// ```no_run
+    /// mod assets {
+    /// #   pub fn get(_path: &str) -> Option<&'static [u8]> {
+    /// #       None
+    /// #   }
+    /// #   #[cfg(any())]
+    ///     include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+    /// }
+    ///
+    /// let index = assets::get("static/index.html").unwrap();
+    /// ```
+    pub fn embed<N>(mut self, name: N) -> ChangeDetectionBuilder
+    where
+        N: Into<PathBuf>,
+    {
+        self.embed = Some(name.into());
+        self
     }
-}
-
-fn collect_resources(path: &Path, filter: &dyn PathMatcher) -> std::io::Result<Vec<PathBuf>> {
-    let mut result = vec![];
 
-    if filter.matches(path.as_ref()) {
-        result.push(path.into());
+    /// Generates a cache-busting manifest of all tracked files in `OUT_DIR`.
+    ///
+    /// See [`AssetManifest`] for the generated content.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use change_detection::{AssetManifest, ChangeDetection};
+    /// ChangeDetection::path("web/dist")
+    ///     .asset_manifest(AssetManifest::new("assets.rs"))
+    ///     .generate();
+    /// ```
+    pub fn asset_manifest(mut self, manifest: AssetManifest) -> ChangeDetectionBuilder {
+        self.asset_manifest = Some(manifest);
+        self
     }
 
+    /// Declares `path` as written by the build script.
+    ///
+    /// Outputs are checked against tracked inputs during generation. An output which is,
+    /// contains or is inside a tracked input makes the build script rerun after every build,
+    /// so it is reported with a warning suggesting the exclude rule that fixes it.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// # use std::path::Path;
+    /// ChangeDetection::exclude(|path: &Path| path.starts_with("web/dist"))
+    ///     .path("web/src")
+    ///     .output("web/dist")
+    ///     .generate();
+    /// ```
+    pub fn output<P>(mut self, path: P) -> ChangeDetectionBuilder
+    where
+        P: Into<PathBuf>,
+    {
+        self.outputs.push(path.into());
+        self
+    }
+
+    /// Fails generation instead of warning if an [`output`](ChangeDetectionBuilder::output)
+    /// overlaps tracked inputs.
+    pub fn deny_overlapping_outputs(mut self) -> ChangeDetectionBuilder {
+        self.deny_overlapping_outputs = true;
+        self
+    }
+
+    /// Fails generation instead of warning if a filter matched nothing or a tracked path
+    /// produced no tracked paths.
+    ///
+    /// Such rules usually went stale after a directory was renamed. Filters are only checked
+    /// against paths passing the enclosing filters.
+    pub fn deny_unmatched_filters(mut self) -> ChangeDetectionBuilder {
+        self.deny_unmatched_filters = true;
+        self
+    }
+
+    /// Warns if more than `max` paths are tracked, listing the largest directories.
+    ///
+    /// Cargo checks every emitted path on every build, so tracking dependencies or build
+    /// outputs like `node_modules` or `target` slows down every build.
+    pub fn max_paths(mut self, max: usize) -> ChangeDetectionBuilder {
+        self.limits.max_paths = Some(max);
+        self
+    }
+
+    /// Warns if scanning tracked paths takes longer than `max`, listing the largest directories.
+    pub fn max_scan_time(mut self, max: std::time::Duration) -> ChangeDetectionBuilder {
+        self.limits.max_scan_time = Some(max);
+        self
+    }
+
+    /// Stops scanning and emits the given paths alone instead of every tracked path once a limit
+    /// is exceeded.
+    ///
+    /// Cargo scans given directories recursively, so changes anywhere inside rerun the build script,
+    /// including paths rejected by filters. A given path containing the target directory or version
+    /// control metadata is replaced by its entries without them, with a warning. Digests, embedded
+    /// files and asset manifests still cover every tracked path, so scanning goes on for them.
+    pub fn fallback_to_roots(mut self) -> ChangeDetectionBuilder {
+        self.limits.fallback_to_roots = true;
+        self
+    }
+
+    /// Skips paths ignored by `.gitignore` and `.ignore` files and `.git/info/exclude`.
+    ///
+    /// Ignore files are read in every walked directory and its parents up to the repository root,
+    /// with full gitignore semantics. Ignored directories are never walked, and directories
+    /// containing ignored entries aren't tracked themselves, only their other entries are.
+    #[cfg(feature = "ignore-files")]
+    pub fn respect_ignore_files(mut self) -> ChangeDetectionBuilder {
+        for name in ignore_files::IGNORE_FILES {
+            self = self.ignore_file(*name);
+        }
+        self.ignore_files.git_exclude = true;
+        self
+    }
+
+    /// Skips paths ignored by `.changeignore` files, see [`ignore_file`](ChangeDetectionBuilder::ignore_file).
+    ///
+    /// Unlike `.gitignore` they describe what change detection should skip, like a committed `web/dist`.
+    #[cfg(feature = "ignore-files")]
+    pub fn change_ignore(self) -> ChangeDetectionBuilder {
+        self.ignore_file(ignore_files::CHANGE_IGNORE)
+    }
+
+    /// Skips paths ignored by files named `name`, which use gitignore syntax.
+    ///
+    /// They are read in every walked directory and its parents up to the repository root,
+    /// nested files override their parents. Every ignore file read is tracked as well,
+    /// so editing one reruns the build script. Directories containing ignored entries
+    /// aren't tracked themselves.
+    #[cfg(feature = "ignore-files")]
+    pub fn ignore_file<N: Into<String>>(mut self, name: N) -> ChangeDetectionBuilder {
+        let name = name.into();
+        if !self.ignore_files.names.contains(&name) {
+            self.ignore_files.names.push(name);
+        }
+        self
+    }
+
+    /// Walks the target directory, `OUT_DIR` and version control metadata like `.git` as well.
+    ///
+    /// They are excluded by default, since they change on every build or commit. The target
+    /// directory is taken from `CARGO_TARGET_DIR` or detected from `OUT_DIR`. Tracked paths
+    /// given explicitly, like `.git/HEAD`, are never excluded. Directories containing excluded
+    /// ones aren't tracked themselves, since cargo would scan them recursively.
+    pub fn without_default_excludes(mut self) -> ChangeDetectionBuilder {
+        self.no_default_excludes = true;
+        self
+    }
+
+    /// Resets modification times of tracked paths which lie in the future to the current time.
+    ///
+    /// Without it such paths are only reported with a warning. Clock skew, for example
+    /// on network file systems or in extracted archives, makes cargo rerun the build script
+    /// on every build until their modification time has passed.
+    pub fn reset_future_mtimes(mut self) -> ChangeDetectionBuilder {
+        self.reset_future_mtimes = true;
+        self
+    }
+
+    /// Explains whether `path` is tracked, without traversing tracked directories.
+    ///
+    /// The returned [`Decision`] lists every tracked path covering `path` with the results
+    /// of the global and per-path include/exclude filters, and whether `path` is hidden
+    /// by an ancestor which isn't traversed.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// let builder = ChangeDetection::path("web/src");
+    /// println!("cargo:warning={}", builder.explain("web/src/foo.js"));
+    /// builder.generate();
+    /// ```
+    pub fn explain<P: AsRef<Path>>(&self, path: P) -> Decision {
+        let path = path.as_ref();
+
+        let mut roots = vec![];
+        let prune = self.prune();
+        self.explain_roots(path, &prune, &mut vec![], &mut roots);
+
+        Decision {
+            path: path.into(),
+            exists: path.exists(),
+            roots,
+        }
+    }
+
+    /// Creates a [`Task`] running `command` when the paths tracked by this builder change.
+    ///
+    /// The task's outputs are the paths declared with [`output`](ChangeDetectionBuilder::output),
+    /// its state is persisted in `OUT_DIR` under `name`.
+    pub fn task<N>(self, name: N, command: std::process::Command) -> Task
+    where
+        N: Into<String>,
+    {
+        Task::new(name.into(), self, command)
+    }
+
+    /// Generates change detection instructions.
+    ///
+    /// If the `CHANGE_DETECTION_EXPLAIN` environment variable is set to `1`, the tracked paths
+    /// and environment variables are recorded in `OUT_DIR`, and the next run emits
+    /// a `cargo:warning` for everything which changed since then.
+    /// Set it to `hash` to also compare file contents.
+    pub fn generate(self) {
+        self.generate_into(&mut StdoutSink)
+            .expect("error generating instructions");
+    }
+
+    /// Generates change detection instructions and records the state of tracked paths.
+    ///
+    /// Call [`Guard::finish`] once the build script did its work to report every tracked path
+    /// modified in the meantime, which would make cargo rerun the build script on every build.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// let guard = ChangeDetection::path("web/src")
+    ///     .path("web/package.json")
+    ///     .guard();
+    ///
+    /// // run npm
+    ///
+    /// guard.deny().finish().unwrap();
+    /// ```
+    pub fn guard(self) -> Guard {
+        let mut generated = MemorySink::default();
+        self.generate_extended(&mut generated)
+            .expect("error generating instructions");
+        let guard = Guard::new(generated.paths()).expect("error recording tracked paths");
+        finish_generate(generated, &mut StdoutSink).expect("error generating instructions");
+
+        guard
+    }
+
+    /// Writes change detection instructions to `sink` instead of stdout.
+    ///
+    /// Explanations enabled with `CHANGE_DETECTION_EXPLAIN` are written to `sink` as warnings.
+    ///
+    /// # Examples:
+    ///
+    /// To keep the instructions in a file next to other build outputs:
+    ///
+    /// ```no_run
+    /// # use change_detection::{ChangeDetection, FileSink};
+    /// let out_dir = std::env::var("OUT_DIR").unwrap();
+    /// let mut sink = FileSink::create(format!("{}/instructions", out_dir)).unwrap();
+    ///
+    /// ChangeDetection::path("web/src")
+    ///     .generate_into(&mut sink)
+    ///     .unwrap();
+    /// ```
+    pub fn generate_into<S>(self, mut sink: S) -> std::io::Result<()>
+    where
+        S: InstructionSink,
+    {
+        let mut generated = MemorySink::default();
+        self.generate_extended(&mut generated)?;
+
+        finish_generate(generated, &mut sink)
+    }
+
+    /// Writes tracked paths and environment variables to `writer` in the given format.
+    ///
+    /// # Examples:
+    ///
+    /// To let make rebuild a bundle using the same rules as the build script:
+    ///
+    /// ```no_run
+    /// # use change_detection::{ChangeDetection, Format};
+    /// let depfile = std::fs::File::create("target/web.d").unwrap();
+    ///
+    /// ChangeDetection::path("web/src")
+    ///     .path("web/package.json")
+    ///     .generate_with(Format::make("web/dist/index.js"), depfile)
+    ///     .unwrap();
+    /// ```
+    ///
+    /// To print tracked paths as JSON:
+    ///
+    /// ```no_run
+    /// # use change_detection::{ChangeDetection, Format};
+    /// ChangeDetection::path("web/src")
+    ///     .env("WEB_API_URL")
+    ///     .generate_with(Format::Json, std::io::stdout())
+    ///     .unwrap();
+    /// ```
+    pub fn generate_with<W>(self, format: Format, writer: W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let mut sink = format.sink(writer);
+        self.generate_extended(&mut sink)?;
+
+        sink.flush()
+    }
+
+    /// Generates change detection instructions and reports changed groups.
+    ///
+    /// Group fingerprints are persisted in the `OUT_DIR` directory, so this method should
+    /// be called from a build script.
+    ///
+    /// # Examples:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// let groups = ChangeDetection::path("build.rs")
+    ///     .group("web", |g| g.path("web/src"))
+    ///     .group("sql", |g| g.path("sql"))
+    ///     .generate_groups();
+    ///
+    /// if groups.is_dirty("web") == Some(true) {
+    ///     // rebuild the web bundle
+    ///     groups.commit("web").unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `OUT_DIR` environment variable is not set.
+    pub fn generate_groups(self) -> Groups {
+        let out_dir = std::env::var_os("OUT_DIR").expect("OUT_DIR environment variable is not set");
+        let mut generated = MemorySink::default();
+
+        let groups = self
+            .generate_groups_extended(Path::new(&out_dir), &mut generated)
+            .expect("error reading group fingerprints");
+        finish_generate(generated, &mut StdoutSink).expect("error generating instructions");
+
+        groups
+    }
+
+    /// Watches paths passing the filters of this builder and delivers batches of changes to `callback`.
+    ///
+    /// Every changed path is checked against the include and exclude filters, including paths
+    /// created after the watch started, so excluded paths like build outputs never trigger it.
+    ///
+    /// # Examples:
+    ///
+    /// To reload assets in a dev server using the tracking rules of `build.rs`:
+    ///
+    /// ```no_run
+    /// # use change_detection::ChangeDetection;
+    /// let (stoppers, stopper) = std::sync::mpsc::channel();
+    /// let watching = std::thread::spawn(move || {
+    ///     let handle = ChangeDetection::path("static")
+    ///         .watch(|changes| {
+    ///             for change in changes {
+    ///                 println!("{:?} {}", change.kind, change.path.display());
+    ///             }
+    ///         })
+    ///         .unwrap();
+    ///     stoppers.send(handle.stopper()).unwrap();
+    ///     handle.run()
+    /// });
+    /// let stopper = stopper.recv().unwrap();
+    ///
+    /// // serve requests
+    ///
+    /// stopper.stop();
+    /// watching.join().unwrap().unwrap();
+    /// ```
+    #[cfg(feature = "watch")]
+    pub fn watch<F>(self, callback: F) -> std::io::Result<watch::WatchHandle>
+    where
+        F: FnMut(Vec<watch::Change>) + 'static,
+    {
+        let roots = self.roots();
+        let prune = self.prune();
+
+        watch::WatchHandle::new(
+            &roots,
+            &self.prune(),
+            Box::new(move |path| self.tracks(&|_| true, &prune, path)),
+            Box::new(callback),
+        )
+    }
+
+    fn generate_extended(self, sink: &mut dyn InstructionSink) -> std::io::Result<()> {
+        let mut scan = self.scan();
+        let paths = self.collect(&|_| true, &mut scan)?;
+        let emitted = if self.check_limits(&scan, &paths, sink)? {
+            self.fallback_paths(&scan.prune, sink)?
+        } else {
+            paths.clone()
+        };
+        for path in &emitted {
+            sink.rerun_if_changed(path)?;
+        }
+
+        self.generate_extras(&paths, &scan, sink)
+    }
+
+    fn generate_groups_extended(
+        self,
+        out_dir: &Path,
+        sink: &mut dyn InstructionSink,
+    ) -> std::io::Result<Groups> {
+        let hits = Hits::default();
+        let all = |path: &Path| self.filter_include_exclude(path, &hits);
+        // Group fingerprints need every tracked path, so the scan never stops early.
+        let mut scan = Scan {
+            budget: Budget::new(self.limits),
+            ..self.scan()
+        };
+
+        let tracked = self.collect_paths(&all, &mut scan)?;
+        let mut groups = vec![];
+        for (name, group) in &self.groups {
+            let paths = group.collect(&all, &mut scan)?;
+            groups.push((name.clone(), paths, group.collect_env()));
+        }
+        self.unmatched_filters(&hits, &mut scan.findings);
+
+        let all_paths: Vec<PathBuf> = tracked
+            .iter()
+            .chain(groups.iter().flat_map(|(_, paths, _)| paths))
+            .cloned()
+            .collect();
+        let emitted = if self.check_limits(&scan, &all_paths, sink)? {
+            self.fallback_paths(&scan.prune, sink)?
+        } else {
+            all_paths.clone()
+        };
+        for path in &emitted {
+            sink.rerun_if_changed(path)?;
+        }
+
+        self.generate_extras(&all_paths, &scan, sink)?;
+
+        Groups::new(out_dir, groups)
+    }
+
+    fn prune(&self) -> Prune {
+        #[allow(unused_mut)]
+        let mut prune = Prune::new(!self.no_default_excludes);
+        #[cfg(feature = "ignore-files")]
+        {
+            prune.ignore_files = self.ignore_files.clone();
+        }
+
+        prune
+    }
+
+    fn scan(&self) -> Scan {
+        // Digests, embedded files and asset manifests need every tracked path.
+        let needs_every_path =
+            self.digest_env.is_some() || self.embed.is_some() || self.asset_manifest.is_some();
+
+        Scan {
+            budget: Budget::new(self.limits).stopping_early(!needs_every_path),
+            prune: self.prune(),
+            findings: vec![],
+            record: record_dir().filter(|_| std::env::var_os(RECORD_ENV).is_some()),
+        }
+    }
+
+    /// Warns if `tracked` paths exceed the limits, returns `true` if only roots should be emitted.
+    fn check_limits(
+        &self,
+        scan: &Scan,
+        tracked: &[PathBuf],
+        sink: &mut dyn InstructionSink,
+    ) -> std::io::Result<bool> {
+        let exceeded = match scan.budget.exceeded() {
+            Some(exceeded) => exceeded,
+            None => return Ok(false),
+        };
+        let report = scan.budget.report(exceeded, &self.roots(), tracked);
+        sink.warning(&report.to_string())?;
+
+        Ok(scan.budget.falls_back())
+    }
+
+    /// Returns the given paths to emit instead of every tracked path.
+    ///
+    /// Cargo scans directories recursively, so a path containing the target directory or version
+    /// control metadata would rerun the build script after every build. Its entries are emitted
+    /// instead, without the excluded ones.
+    fn fallback_paths(
+        &self,
+        prune: &Prune,
+        sink: &mut dyn InstructionSink,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let mut result = vec![];
+        for root in self.roots() {
+            if reruns_always(&root, prune) {
+                sink.warning(&format!(
+                    "change-detection: {} isn't tracked as a whole, tracking it would rerun the build script after every build",
+                    slash(&root)
+                ))?;
+            }
+            fallback_entries(&root, prune, &mut result)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Checks modification times, outputs and rule coverage `findings` and writes instructions following
+    /// `tracked` paths: environment variables, digests and cfgs.
+    fn generate_extras(
+        &self,
+        tracked: &[PathBuf],
+        scan: &Scan,
+        sink: &mut dyn InstructionSink,
+    ) -> std::io::Result<()> {
+        logging::debug(|| {
+            format!(
+                "tracked {} paths and {} environment variables",
+                tracked.len(),
+                self.collect_env().len()
+            )
+        });
+
+        let now = std::time::SystemTime::now();
+        for future in future::future_mtimes(tracked, now)? {
+            let reset = if self.reset_future_mtimes {
+                Some(future.reset(now))
+            } else {
+                None
+            };
+            sink.warning(&future.message(reset))?;
+        }
+        for overlap in output::overlaps(&self.outputs, tracked) {
+            if self.deny_overlapping_outputs {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    overlap.to_string(),
+                ));
+            }
+            sink.warning(&overlap.to_string())?;
+        }
+        for finding in &scan.findings {
+            if self.deny_unmatched_filters {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    finding.to_string(),
+                ));
+            }
+            sink.warning(&finding.to_string())?;
+        }
+        for name in self.collect_env() {
+            sink.rerun_if_env_changed(&name)?;
+        }
+        match &scan.record {
+            Some(dir) => record_tracked(dir, tracked)?,
+            // A record of an earlier run would describe other paths.
+            None => {
+                if let Some(dir) = record_dir() {
+                    remove_records(&dir, None)?;
+                }
+            }
+        }
+        if let Some(name) = &self.digest_env {
+            sink.rustc_env(name, &digest::digest(tracked)?)?;
+        }
+        if let Some(name) = &self.embed {
+            embed::write(&out_dir()?.join(name), tracked)?;
+        }
+        if let Some(manifest) = &self.asset_manifest {
+            manifest.write(&out_dir()?, tracked)?;
+        }
+        let prune = self.prune();
+        for (name, path) in &self.cfgs {
+            sink.rustc_check_cfg(name)?;
+            // Cargo reruns the build script after every build while a tracked path is missing,
+            // so a missing path is tracked through its nearest existing ancestor instead.
+            let tracked = if path.exists() {
+                sink.rustc_cfg(name)?;
+                Some(path.as_path())
+            } else {
+                existing_ancestor(path)
+            };
+            match tracked {
+                Some(tracked) if reruns_always(tracked, &prune) => {
+                    sink.warning(&format!(
+                        "change-detection: {} isn't tracked for cfg {}, tracking {} would rerun the build script after every build",
+                        slash(path),
+                        name,
+                        slash(tracked)
+                    ))?;
+                }
+                Some(tracked) => sink.rerun_if_changed(tracked)?,
+                None => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect(
+        &self,
+        parent: &dyn Fn(&Path) -> bool,
+        scan: &mut Scan,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let hits = Hits::default();
+        let filter = |path: &Path| parent(path) && self.filter_include_exclude(path, &hits);
+
+        let mut result = self.collect_paths(&filter, scan)?;
+        for (_, group) in &self.groups {
+            result.extend(group.collect(&filter, scan)?);
+        }
+        if !scan.budget.stopped() {
+            self.unmatched_filters(&hits, &mut scan.findings);
+        }
+
+        Ok(result)
+    }
+
+    fn unmatched_filters(&self, hits: &Hits, findings: &mut Vec<Finding>) {
+        hits.unmatched(
+            &self.scope(),
+            self.include.is_some(),
+            self.exclude.is_some(),
+            findings,
+        );
+    }
+
+    #[cfg(feature = "watch")]
+    fn tracks(&self, parent: &dyn Fn(&Path) -> bool, prune: &Prune, path: &Path) -> bool {
+        let hits = Hits::default();
+        let filter = |path: &Path| parent(path) && self.filter_include_exclude(path, &hits);
+
+        self.paths.iter().any(|p| p.tracks(&filter, prune, path))
+            || self
+                .groups
+                .iter()
+                .any(|(_, group)| group.tracks(&filter, prune, path))
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        let mut result: Vec<PathBuf> = self.paths.iter().map(|p| p.path().into()).collect();
+        for (_, group) in &self.groups {
+            result.extend(group.roots());
+        }
+
+        result
+    }
+
+    fn collect_env(&self) -> Vec<String> {
+        let mut result = self.env.clone();
+        for (_, group) in &self.groups {
+            result.extend(group.collect_env());
+        }
+
+        result
+    }
+
+    fn collect_paths(
+        &self,
+        filter: &dyn Fn(&Path) -> bool,
+        scan: &mut Scan,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let mut result = vec![];
+        for path in &self.paths {
+            result.extend(path.collect(filter, scan)?);
+        }
+
+        Ok(result)
+    }
+
+    fn filter_include_exclude(&self, path: &Path, hits: &Hits) -> bool {
+        let decision = self.filter_decision(path);
+        hits.record(decision);
+        if let Some(filter) = decision.rejected_by() {
+            logging::trace(|| {
+                format!(
+                    "rejected {} by the {} filter of {}",
+                    path.display(),
+                    filter,
+                    self.scope()
+                )
+            });
+        }
+
+        decision.accepts()
+    }
+
+    fn filter_decision(&self, path: &Path) -> FilterDecision {
+        FilterDecision::new(self.include.as_deref(), self.exclude.as_deref(), path)
+    }
+
+    /// Describes this builder or group in logs and explanations.
+    fn scope(&self) -> String {
+        match &self.name {
+            Some(name) => format!("group {}", name),
+            None => "the builder".to_owned(),
+        }
+    }
+
+    fn explain_roots<'a>(
+        &'a self,
+        path: &Path,
+        prune: &Prune,
+        parents: &mut Vec<&'a ChangeDetectionBuilder>,
+        result: &mut Vec<RootDecision>,
+    ) {
+        parents.push(self);
+
+        let normalized = output::normalize(path);
+        for root in &self.paths {
+            let relative = match normalized.strip_prefix(output::normalize(root.path())) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            // The traversal passes paths joined to the root as it was given.
+            let path = if relative.as_os_str().is_empty() {
+                root.path().to_path_buf()
+            } else {
+                root.path().join(relative)
+            };
+
+            result.push(RootDecision {
+                root: root.path().into(),
+                group: self.name.clone(),
+                global: parents
+                    .iter()
+                    .map(|scope| (scope.scope(), scope.filter_decision(&path)))
+                    .collect(),
+                path: root.filter_decision(&path),
+                hidden_by: decision::hidden_by(root.path(), &path, &prune.below(root.path())),
+            });
+        }
+
+        for (_, group) in &self.groups {
+            group.explain_roots(path, prune, parents, result);
+        }
+
+        parents.pop();
+    }
+}
+
+/// State of one traversal of tracked paths.
+struct Scan {
+    budget: Budget,
+    prune: Prune,
+    findings: Vec<Finding>,
+    /// Where tracked paths are recorded, if [`RECORD_ENV`] asks for it.
+    record: Option<PathBuf>,
+}
+
+/// State of the traversal of one tracked path.
+struct Walk<'a> {
+    budget: &'a Budget,
+    pruned: Pruned,
+    visited: usize,
+}
+
+/// Returns `true` if tracking `path` as a whole makes cargo rerun the build script after every build.
+fn reruns_always(path: &Path, prune: &Prune) -> bool {
+    path == Path::new(".") || prune.contained_in(path)
+}
+
+/// Adds `path` to `result`, or its entries without the excluded ones if it [`reruns_always`].
+fn fallback_entries(path: &Path, prune: &Prune, result: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() || !reruns_always(path, prune) {
+        result.push(path.into());
+        return Ok(());
+    }
+
+    #[allow(unused_mut)]
+    let mut pruned = prune.below(path);
+    #[cfg(feature = "ignore-files")]
+    pruned.enter(path);
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?.path();
+        if !pruned.prunes(&entry) {
+            fallback_entries(&entry, prune, result)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the nearest existing ancestor of `path`, where its creation or deletion can be observed.
+pub(crate) fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors()
+        .skip(1)
+        .map(|ancestor| {
+            if ancestor.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                ancestor
+            }
+        })
+        .find(|ancestor| ancestor.is_dir())
+}
+
+/// Returns `path` relative to `base` with forward slashes, or all of `path` if it is outside of `base`.
+pub(crate) fn relative_slash(path: &Path, base: &Path) -> String {
+    sink::slash(path.strip_prefix(base).unwrap_or(path))
+}
+
+/// Returns files among `paths` keyed by their [`relative_slash`] paths, sorted and deduplicated.
+///
+/// Directories are skipped, their content is tracked as separate paths.
+pub(crate) fn relative_files(base: &Path, paths: &[PathBuf]) -> Vec<(String, PathBuf)> {
+    let mut files: Vec<(String, PathBuf)> = paths
+        .iter()
+        .filter(|path| path.is_file())
+        .map(|path| (relative_slash(path, base), base.join(path)))
+        .collect();
+    files.sort();
+    files.dedup_by(|a, b| a.0 == b.0);
+
+    files
+}
+
+fn out_dir() -> std::io::Result<PathBuf> {
+    std::env::var_os("OUT_DIR")
+        .map(PathBuf::from)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "OUT_DIR environment variable is not set",
+            )
+        })
+}
+
+/// Asks build scripts to record their tracked paths, set by `cargo change-detection watch`.
+const RECORD_ENV: &str = "CHANGE_DETECTION_RECORD";
+
+/// Returns the directory in `OUT_DIR` tracked paths are recorded in.
+fn record_dir() -> Option<PathBuf> {
+    std::env::var_os("OUT_DIR").map(|out_dir| PathBuf::from(out_dir).join("change-detection"))
+}
+
+/// Records `tracked` paths in `dir`, so `cargo change-detection watch` knows exactly which
+/// paths pass the include/exclude filters, instead of watching whole tracked directories.
+///
+/// A build script may generate instructions several times in one run, each call writes its own
+/// `tracked-*` file and files of earlier runs are removed.
+fn record_tracked(dir: &Path, tracked: &[PathBuf]) -> std::io::Result<()> {
+    let mut contents = String::new();
+    for path in tracked {
+        contents.push_str(&slash(path));
+        contents.push('\n');
+    }
+
+    let run = format!("tracked-{}-", std::process::id());
+    remove_records(dir, Some(&run))?;
+    std::fs::create_dir_all(dir)?;
+    write_if_changed(
+        dir.join(format!(
+            "{}{:016x}",
+            run,
+            hash::hash_bytes(contents.as_bytes())
+        )),
+        contents,
+    )?;
+
+    Ok(())
+}
+
+/// Removes `tracked-*` files from `dir`, except those whose names start with `keep`.
+fn remove_records(dir: &Path, keep: Option<&str>) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with("tracked-") && !keep.is_some_and(|keep| name.starts_with(keep)) {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes generated instructions followed by explanations of changes to `sink`.
+fn finish_generate(generated: MemorySink, sink: &mut dyn InstructionSink) -> std::io::Result<()> {
+    generated.replay(sink)?;
+    explain::explain_changes(&generated.paths(), &generated.env(), sink)?;
+
+    sink.flush()
+}
+
+pub enum ChangeDetectionPath {
+    Path(PathBuf),
+    PathInclude(PathBuf, Box<dyn PathMatcher>),
+    PathExclude(PathBuf, Box<dyn PathMatcher>),
+    PathIncludeExclude {
+        path: PathBuf,
+        include: Box<dyn PathMatcher>,
+        exclude: Box<dyn PathMatcher>,
+    },
+}
+
+impl ChangeDetectionPath {
+    fn path(&self) -> &Path {
+        match self {
+            ChangeDetectionPath::Path(path)
+            | ChangeDetectionPath::PathInclude(path, _)
+            | ChangeDetectionPath::PathExclude(path, _)
+            | ChangeDetectionPath::PathIncludeExclude { path, .. } => path,
+        }
+    }
+
+    fn matches(&self, base: &dyn Fn(&Path) -> bool, p: &Path, hits: &Hits) -> bool {
+        if !base(p) {
+            return false;
+        }
+
+        let decision = self.filter_decision(p);
+        hits.record(decision);
+        if let Some(filter) = decision.rejected_by() {
+            logging::trace(|| {
+                format!(
+                    "rejected {} by the {} filter of path {}",
+                    p.display(),
+                    filter,
+                    self.path().display()
+                )
+            });
+        }
+
+        decision.accepts()
+    }
+
+    fn filter_decision(&self, p: &Path) -> FilterDecision {
+        match self {
+            ChangeDetectionPath::Path(_) => FilterDecision::new(None, None, p),
+            ChangeDetectionPath::PathInclude(_, include) => {
+                FilterDecision::new(Some(include.as_ref()), None, p)
+            }
+            ChangeDetectionPath::PathExclude(_, exclude) => {
+                FilterDecision::new(None, Some(exclude.as_ref()), p)
+            }
+            ChangeDetectionPath::PathIncludeExclude {
+                include, exclude, ..
+            } => FilterDecision::new(Some(include.as_ref()), Some(exclude.as_ref()), p),
+        }
+    }
+
+    fn collect(
+        &self,
+        base: &dyn Fn(&Path) -> bool,
+        scan: &mut Scan,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        logging::debug(|| format!("collecting {}", self.path().display()));
+
+        let hits = Hits::default();
+        let mut walk = Walk {
+            budget: &scan.budget,
+            pruned: scan.prune.below(self.path()),
+            visited: 0,
+        };
+        let mut result = vec![];
+        collect_resources(
+            self.path(),
+            &|p: &Path| self.matches(base, p, &hits),
+            &mut walk,
+            &mut result,
+        )?;
+
+        logging::debug(|| {
+            format!(
+                "tracked {} of {} visited paths in {}",
+                result.len(),
+                walk.visited,
+                self.path().display()
+            )
+        });
+
+        let (has_include, has_exclude) = match self {
+            ChangeDetectionPath::Path(_) => (false, false),
+            ChangeDetectionPath::PathInclude(..) => (true, false),
+            ChangeDetectionPath::PathExclude(..) => (false, true),
+            ChangeDetectionPath::PathIncludeExclude { .. } => (true, true),
+        };
+        // A scan which stopped early didn't visit everything the rules might match.
+        if !scan.budget.stopped() {
+            hits.unmatched(
+                &format!("path {}", slash(self.path())),
+                has_include,
+                has_exclude,
+                &mut scan.findings,
+            );
+            Finding::empty_root(self.path(), &result, &mut scan.findings);
+        }
+        #[cfg(feature = "ignore-files")]
+        let result = walk.pruned.with_ignore_files(result);
+
+        Ok(result)
+    }
+
+    /// Returns `true` if `path` lies under this path and passes its filters.
+    #[cfg(feature = "watch")]
+    fn tracks(&self, base: &dyn Fn(&Path) -> bool, prune: &Prune, path: &Path) -> bool {
+        path.starts_with(self.path())
+            && prune.below(self.path()).hidden(self.path(), path).is_none()
+            && self.matches(base, path, &Hits::default())
+    }
+}
+
+impl<T> From<T> for ChangeDetectionPath
+where
+    T: AsRef<Path>,
+{
+    fn from(path: T) -> Self {
+        ChangeDetectionPath::Path(path.as_ref().into())
+    }
+}
+
+/// Adds paths below `path` passing `filter` to `result`, returns `true` if `path` contains pruned entries.
+fn collect_resources(
+    path: &Path,
+    filter: &dyn PathMatcher,
+    walk: &mut Walk,
+    result: &mut Vec<PathBuf>,
+) -> std::io::Result<bool> {
+    if walk.budget.stopped() {
+        return Ok(false);
+    }
+
+    walk.visited += 1;
+    let tracked = filter.matches(path.as_ref());
+    let position = result.len();
+    if tracked {
+        logging::trace(|| format!("tracked {}", path.display()));
+        result.push(path.into());
+    }
+
+    walk.budget.visit(tracked);
     if !path.is_dir() {
-        return Ok(result);
+        return Ok(false);
     }
 
-    for entry in std::fs::read_dir(&path)? {
+    #[cfg(feature = "ignore-files")]
+    walk.pruned.enter(path);
+
+    let mut hides_pruned = false;
+    for entry in std::fs::read_dir(path)? {
         let entry = entry?;
         let path = entry.path();
+        if walk.pruned.prunes(&path) {
+            logging::trace(|| format!("pruned {}", path.display()));
+            hides_pruned = true;
+            continue;
+        }
 
-        let nested = collect_resources(path.as_ref(), filter)?;
-        result.extend(nested);
+        hides_pruned |= collect_resources(path.as_ref(), filter, walk, result)?;
     }
 
-    Ok(result)
+    #[cfg(feature = "ignore-files")]
+    walk.pruned.leave();
+
+    // Cargo scans tracked directories recursively, pruned entries at any depth included,
+    // so such a directory is left out and its other entries are tracked instead.
+    if tracked && hides_pruned {
+        logging::trace(|| format!("untracked {} containing pruned entries", path.display()));
+        result.remove(position);
+    }
+
+    Ok(hides_pruned)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{ChangeDetection, ChangeDetectionBuilder};
+    use super::{ChangeDetection, ChangeDetectionBuilder, Instruction, MemorySink};
     use std::path::{Path, PathBuf};
 
     fn assert_change_detection(builder: ChangeDetectionBuilder, expected: &[&str]) {
-        let mut result: Vec<PathBuf> = vec![];
-        let r = &mut result;
+        let mut sink = MemorySink::default();
+        builder.generate_extended(&mut sink).unwrap();
+        let mut result = sink.paths();
 
-        builder.generate_extended(move |path| r.push(path.into()));
-
-        let mut expected = expected
-            .iter()
-            .map(|s| PathBuf::from(s))
-            .collect::<Vec<_>>();
+        let mut expected = expected.iter().map(PathBuf::from).collect::<Vec<_>>();
 
         expected.sort();
         result.sort();
@@ -616,6 +2053,20 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn filters_need_not_be_send() {
+        let visited = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = visited.clone();
+
+        ChangeDetection::path_include("fixtures-01", move |_: &Path| {
+            counter.set(counter.get() + 1);
+            true
+        })
+        .generate();
+
+        assert_eq!(visited.get(), 7);
+    }
+
     #[test]
     fn single_file() {
         assert_change_detection(ChangeDetection::path("src/lib.rs"), &["src/lib.rs"]);
@@ -623,7 +2074,39 @@ mod tests {
 
     #[test]
     fn single_path() {
-        assert_change_detection(ChangeDetection::path("src"), &["src", "src/lib.rs"]);
+        assert_change_detection(
+            ChangeDetection::path("src"),
+            &[
+                "src",
+                "src/assets.rs",
+                "src/coverage.rs",
+                "src/decision.rs",
+                "src/digest.rs",
+                "src/embed.rs",
+                "src/explain.rs",
+                "src/format.rs",
+                "src/future.rs",
+                "src/group.rs",
+                "src/guard.rs",
+                "src/hash.rs",
+                "src/ignore_files.rs",
+                "src/lib.rs",
+                "src/limits.rs",
+                "src/lock.rs",
+                "src/logging.rs",
+                "src/manifest.rs",
+                "src/output.rs",
+                "src/prune.rs",
+                "src/sink.rs",
+                "src/task.rs",
+                "src/test_utils.rs",
+                "src/watch",
+                "src/watch/inotify.rs",
+                "src/watch/mod.rs",
+                "src/watch/poll.rs",
+                "src/write.rs",
+            ],
+        );
     }
 
     #[test]
@@ -773,4 +2256,408 @@ mod tests {
             ],
         );
     }
+
+    #[test]
+    fn digest_env_covers_filtered_files() {
+        let digest = |builder: ChangeDetectionBuilder| {
+            let mut sink = MemorySink::default();
+            builder
+                .digest_env("ASSETS_DIGEST")
+                .generate_extended(&mut sink)
+                .unwrap();
+            match sink.instructions.last() {
+                Some(Instruction::RustcEnv { name, value }) if name == "ASSETS_DIGEST" => {
+                    value.clone()
+                }
+                other => panic!("unexpected instruction {:?}", other),
+            }
+        };
+
+        let all = digest(ChangeDetection::path("fixtures-01"));
+        let filtered = digest(ChangeDetection::path_exclude(
+            "fixtures-01",
+            |path: &Path| path.ends_with("a"),
+        ));
+
+        assert_eq!(all, digest(ChangeDetection::path("fixtures-01")));
+        assert_ne!(all, filtered);
+    }
+
+    #[test]
+    fn cfg_if_exists_tracks_path_or_existing_ancestor() {
+        let mut sink = MemorySink::default();
+        ChangeDetection::path("fixtures-01/a")
+            .cfg_if_exists("has_fixtures", "fixtures-02")
+            .cfg_if_exists("has_web_ui", "fixtures-04/web/dist")
+            .cfg_if_exists("has_docs", "docs/book")
+            .generate_extended(&mut sink)
+            .unwrap();
+
+        assert_eq!(
+            sink.instructions,
+            vec![
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-01/a")),
+                Instruction::RustcCheckCfg("has_fixtures".into()),
+                Instruction::RustcCfg("has_fixtures".into()),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-02")),
+                Instruction::RustcCheckCfg("has_web_ui".into()),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-04")),
+                Instruction::RustcCheckCfg("has_docs".into()),
+                Instruction::Warning(
+                    "change-detection: docs/book isn't tracked for cfg has_docs, tracking . would rerun the build script after every build".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cfg_if_exists_respects_without_default_excludes() {
+        let tmp = crate::test_utils::TempDir::new("cfg-default-excludes");
+        std::fs::create_dir_all(tmp.path().join(".git")).unwrap();
+
+        let cfg = |builder: ChangeDetectionBuilder| {
+            let mut sink = MemorySink::default();
+            builder
+                .cfg_if_exists("has_repo", tmp.path())
+                .generate_extended(&mut sink)
+                .unwrap();
+            sink.instructions.pop().unwrap()
+        };
+
+        assert!(matches!(
+            cfg(ChangeDetection::path("fixtures-01/a")),
+            Instruction::Warning(_)
+        ));
+        assert_eq!(
+            cfg(ChangeDetection::path("fixtures-01/a").without_default_excludes()),
+            Instruction::RerunIfChanged(tmp.path().into())
+        );
+    }
+
+    #[test]
+    fn prunes_vcs_metadata_by_default() {
+        let tmp = crate::test_utils::TempDir::new("default-excludes");
+        std::fs::create_dir_all(tmp.path().join(".git")).unwrap();
+        std::fs::write(tmp.path().join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        std::fs::write(tmp.path().join("build.rs"), "fn main() {}").unwrap();
+
+        assert_change_detection(
+            ChangeDetection::path(tmp.path()),
+            &[tmp.path().join("build.rs").to_str().unwrap()],
+        );
+        assert_change_detection(
+            ChangeDetection::path(tmp.path()).without_default_excludes(),
+            &[
+                tmp.path().to_str().unwrap(),
+                tmp.path().join(".git").to_str().unwrap(),
+                tmp.path().join(".git/HEAD").to_str().unwrap(),
+                tmp.path().join("build.rs").to_str().unwrap(),
+            ],
+        );
+        assert_change_detection(
+            ChangeDetection::path(tmp.path().join(".git/HEAD")),
+            &[tmp.path().join(".git/HEAD").to_str().unwrap()],
+        );
+
+        let decision = ChangeDetection::path(tmp.path()).explain(tmp.path().join(".git/HEAD"));
+        assert_eq!(
+            decision.roots[0].hidden_by,
+            Some(crate::Hidden::Pruned(tmp.path().join(".git")))
+        );
+    }
+
+    #[test]
+    fn leaves_out_directories_containing_pruned_entries() {
+        let tmp = crate::test_utils::TempDir::new("pruned-parent");
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("build.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+
+        let mut scan = super::Scan {
+            budget: super::Budget::new(super::Limits::default()),
+            prune: super::Prune::new(true).with_dir(&root.join("target")),
+            findings: vec![],
+            record: None,
+        };
+        let mut paths = ChangeDetection::path(root)
+            .collect(&|_| true, &mut scan)
+            .unwrap();
+        paths.sort();
+
+        assert_eq!(
+            paths,
+            vec![
+                root.join("build.rs"),
+                root.join("src"),
+                root.join("src/lib.rs"),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaves_out_every_ancestor_of_nested_pruned_entries() {
+        let tmp = crate::test_utils::TempDir::new("pruned-ancestors");
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("a/b/.git")).unwrap();
+        std::fs::create_dir_all(root.join("c")).unwrap();
+        std::fs::write(root.join("a/b/.git/HEAD"), "ref: refs/heads/main").unwrap();
+        std::fs::write(root.join("a/b/lib.rs"), "").unwrap();
+        std::fs::write(root.join("c/lib.rs"), "").unwrap();
+
+        assert_change_detection(
+            ChangeDetection::path(root),
+            &[
+                root.join("a/b/lib.rs").to_str().unwrap(),
+                root.join("c").to_str().unwrap(),
+                root.join("c/lib.rs").to_str().unwrap(),
+            ],
+        );
+    }
+
+    #[cfg(feature = "ignore-files")]
+    #[test]
+    fn respects_ignore_files() {
+        let tmp = crate::test_utils::TempDir::new("respect-ignore-files");
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("build")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        std::fs::write(root.join("app.js"), "").unwrap();
+        std::fs::write(root.join("app.log"), "").unwrap();
+        std::fs::write(root.join("build/app.js"), "").unwrap();
+
+        assert_change_detection(
+            ChangeDetection::path(root).respect_ignore_files(),
+            &[
+                root.join(".gitignore").to_str().unwrap(),
+                root.join("app.js").to_str().unwrap(),
+            ],
+        );
+
+        let decision = ChangeDetection::path(root)
+            .respect_ignore_files()
+            .explain(root.join("build/app.js"));
+        assert_eq!(
+            decision.roots[0].hidden_by,
+            Some(crate::Hidden::Ignored(root.join("build")))
+        );
+    }
+
+    #[cfg(feature = "ignore-files")]
+    #[test]
+    fn leaves_out_directories_containing_nested_ignored_entries() {
+        let tmp = crate::test_utils::TempDir::new("nested-ignored");
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("static/sub")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(root.join("static/index.html"), "").unwrap();
+        std::fs::write(root.join("static/sub/app.js"), "").unwrap();
+        std::fs::write(root.join("static/sub/debug.log"), "").unwrap();
+
+        assert_change_detection(
+            ChangeDetection::path(root.join("static")).respect_ignore_files(),
+            &[
+                root.join(".gitignore").to_str().unwrap(),
+                root.join("static/index.html").to_str().unwrap(),
+                root.join("static/sub/app.js").to_str().unwrap(),
+            ],
+        );
+    }
+
+    #[cfg(feature = "ignore-files")]
+    #[test]
+    fn tracks_change_ignore_files() {
+        let tmp = crate::test_utils::TempDir::new("change-ignore");
+        let web = tmp.path().join("web");
+        std::fs::create_dir_all(web.join("dist")).unwrap();
+        std::fs::create_dir_all(web.join("src")).unwrap();
+        std::fs::write(web.join(".changeignore"), "dist/\n*.map\n").unwrap();
+        std::fs::write(web.join("src/.changeignore"), "!keep.map\n").unwrap();
+        for file in ["dist/app.js", "src/app.js", "src/app.map", "src/keep.map"] {
+            std::fs::write(web.join(file), "").unwrap();
+        }
+
+        assert_change_detection(
+            ChangeDetection::path_exclude(&web, |path: &Path| path.ends_with(".changeignore"))
+                .change_ignore(),
+            &[
+                web.join(".changeignore").to_str().unwrap(),
+                web.join("src/.changeignore").to_str().unwrap(),
+                web.join("src/app.js").to_str().unwrap(),
+                web.join("src/keep.map").to_str().unwrap(),
+            ],
+        );
+    }
+
+    #[cfg(feature = "ignore-files")]
+    #[test]
+    fn leaves_out_directories_containing_nested_change_ignored_entries() {
+        let tmp = crate::test_utils::TempDir::new("nested-change-ignore");
+        let web = tmp.path().join("web");
+        std::fs::create_dir_all(web.join("src/gen")).unwrap();
+        std::fs::write(web.join(".changeignore"), "src/gen/\n").unwrap();
+        std::fs::write(web.join("package.json"), "{}").unwrap();
+        std::fs::write(web.join("src/app.js"), "").unwrap();
+        std::fs::write(web.join("src/gen/api.js"), "").unwrap();
+
+        assert_change_detection(
+            ChangeDetection::path(&web).change_ignore(),
+            &[
+                web.join(".changeignore").to_str().unwrap(),
+                web.join("package.json").to_str().unwrap(),
+                web.join("src/app.js").to_str().unwrap(),
+            ],
+        );
+    }
+
+    #[test]
+    fn records_tracked_paths_of_every_call() {
+        let tmp = crate::test_utils::TempDir::new("record-tracked");
+        let dir = tmp.path().join("change-detection");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tracked-0-0000000000000000"), "old\n").unwrap();
+
+        super::record_tracked(&dir, &[PathBuf::from("x")]).unwrap();
+        super::record_tracked(&dir, &[PathBuf::from("y"), "x".into()]).unwrap();
+
+        let mut recorded: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        recorded.sort();
+        assert_eq!(recorded, ["x\n", "y\nx\n"]);
+
+        super::remove_records(&dir, None).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn falls_back_to_roots_over_the_limit() {
+        let generate = |builder: ChangeDetectionBuilder| {
+            let mut sink = MemorySink::default();
+            builder
+                .path("fixtures-02")
+                .digest_env("FIXTURES_DIGEST")
+                .generate_extended(&mut sink)
+                .unwrap();
+            sink.instructions
+        };
+
+        let all = generate(ChangeDetection::path("fixtures-01"));
+        let fallback = generate(
+            ChangeDetection::path("fixtures-01")
+                .max_paths(3)
+                .fallback_to_roots(),
+        );
+
+        assert_eq!(
+            fallback[..3],
+            [
+                Instruction::Warning(
+                    "change-detection: tracked paths exceed the limit of 3, \
+                     the largest directories are fixtures-01 (7), fixtures-02 (4), tracking the given paths only"
+                        .into()
+                ),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-01")),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-02")),
+            ]
+        );
+        // The digest still covers every tracked file, not the given paths alone.
+        assert_eq!(fallback.last(), all.last());
+        assert!(matches!(
+            fallback.last(),
+            Some(Instruction::RustcEnv { name, .. }) if name == "FIXTURES_DIGEST"
+        ));
+
+        // Without outputs needing every tracked path, the scan stops at the limit.
+        let builder = ChangeDetection::path("fixtures-01")
+            .path("fixtures-02")
+            .max_paths(3)
+            .fallback_to_roots();
+        let mut scan = builder.scan();
+        let paths = builder.collect(&|_| true, &mut scan).unwrap();
+        assert_eq!(paths.len(), 4);
+        assert!(scan.budget.stopped());
+
+        let mut sink = MemorySink::default();
+        builder.generate_extended(&mut sink).unwrap();
+        assert_eq!(
+            sink.instructions,
+            [
+                Instruction::Warning(
+                    "change-detection: tracked paths exceed the limit of 3, \
+                     the largest directories are fixtures-01 (4), tracking the given paths only"
+                        .into()
+                ),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-01")),
+                Instruction::RerunIfChanged(PathBuf::from("fixtures-02")),
+            ]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_entries_of_roots_containing_excluded_directories() {
+        let tmp = crate::test_utils::TempDir::new("fallback-pruned");
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("build.rs"), "fn main() {}").unwrap();
+
+        let mut sink = MemorySink::default();
+        ChangeDetection::path(root)
+            .max_paths(1)
+            .fallback_to_roots()
+            .generate_extended(&mut sink)
+            .unwrap();
+
+        let mut emitted = sink.paths();
+        emitted.sort();
+        assert_eq!(emitted, vec![root.join("build.rs"), root.join("src")]);
+        assert!(sink.instructions.contains(&Instruction::Warning(format!(
+            "change-detection: {} isn't tracked as a whole, tracking it would rerun the build script after every build",
+            crate::sink::slash(root)
+        ))));
+    }
+
+    #[test]
+    fn reports_unmatched_filters_and_empty_roots() {
+        let builder = || {
+            ChangeDetection::exclude(|path: &Path| path.ends_with("tmp"))
+                .path_include("fixtures-01", |path: &Path| !path.ends_with("c"))
+                .path("fixtures-05")
+        };
+
+        let mut sink = MemorySink::default();
+        builder().generate_extended(&mut sink).unwrap();
+        let warnings: Vec<_> = sink
+            .instructions
+            .iter()
+            .filter(|instruction| matches!(instruction, Instruction::Warning(_)))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                &Instruction::Warning(
+                    "change-detection: path fixtures-05 produced no tracked paths".into()
+                ),
+                &Instruction::Warning(
+                    "change-detection: the exclude filter of the builder matched nothing".into()
+                ),
+            ]
+        );
+
+        let result = builder()
+            .deny_unmatched_filters()
+            .generate_extended(&mut MemorySink::default());
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+
+        // Tracking directories alone is fine, cargo scans them recursively.
+        ChangeDetection::path_exclude("fixtures-04", |path: &Path| !path.is_dir())
+            .deny_unmatched_filters()
+            .generate_extended(&mut MemorySink::default())
+            .unwrap();
+    }
 }
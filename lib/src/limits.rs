@@ -0,0 +1,289 @@
+use crate::sink::slash;
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// How many of the largest directories a warning lists.
+const LARGEST: usize = 5;
+
+/// Limits on the tracked paths, set with
+/// [`max_paths`](crate::ChangeDetectionBuilder::max_paths) and
+/// [`max_scan_time`](crate::ChangeDetectionBuilder::max_scan_time).
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Limits {
+    pub(crate) max_paths: Option<usize>,
+    pub(crate) max_scan_time: Option<Duration>,
+    pub(crate) fallback_to_roots: bool,
+}
+
+/// Tracks a traversal against [`Limits`].
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: Limits,
+    start: Instant,
+    tracked: Cell<usize>,
+    exceeded: Cell<Option<Exceeded>>,
+    stopping_early: bool,
+}
+
+/// The first limit a traversal exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Exceeded {
+    Paths(usize),
+    ScanTime(Duration),
+}
+
+impl Budget {
+    pub(crate) fn new(limits: Limits) -> Budget {
+        Budget {
+            limits,
+            start: Instant::now(),
+            tracked: Cell::new(0),
+            exceeded: Cell::new(None),
+            stopping_early: false,
+        }
+    }
+
+    /// Stops the traversal once a limit is exceeded and only roots are emitted.
+    ///
+    /// Traversals for digests and other outputs needing every tracked path go on.
+    pub(crate) fn stopping_early(mut self, stopping_early: bool) -> Budget {
+        self.stopping_early = stopping_early;
+        self
+    }
+
+    /// Records a visited path.
+    pub(crate) fn visit(&self, tracked: bool) {
+        if tracked {
+            self.tracked.set(self.tracked.get() + 1);
+        }
+
+        if self.exceeded.get().is_none() {
+            if let Some(max) = self
+                .limits
+                .max_paths
+                .filter(|max| self.tracked.get() > *max)
+            {
+                self.exceeded.set(Some(Exceeded::Paths(max)));
+            } else if let Some(max) = self
+                .limits
+                .max_scan_time
+                .filter(|max| self.start.elapsed() > *max)
+            {
+                self.exceeded.set(Some(Exceeded::ScanTime(max)));
+            }
+        }
+    }
+
+    /// Returns `true` if a limit was exceeded and only roots are emitted.
+    pub(crate) fn falls_back(&self) -> bool {
+        self.limits.fallback_to_roots && self.exceeded.get().is_some()
+    }
+
+    /// Returns `true` if the traversal should stop, it exceeded a limit and only roots are emitted.
+    pub(crate) fn stopped(&self) -> bool {
+        self.stopping_early && self.falls_back()
+    }
+
+    pub(crate) fn exceeded(&self) -> Option<Exceeded> {
+        self.exceeded.get()
+    }
+
+    /// Returns the warning about the exceeded limit, listing the largest directories of `tracked`.
+    pub(crate) fn report(
+        &self,
+        exceeded: Exceeded,
+        roots: &[PathBuf],
+        tracked: &[PathBuf],
+    ) -> LimitExceeded {
+        LimitExceeded {
+            exceeded,
+            tracked: tracked.len(),
+            largest: largest(roots, tracked),
+            fallback_to_roots: self.limits.fallback_to_roots,
+        }
+    }
+}
+
+/// A warning about an exceeded limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LimitExceeded {
+    exceeded: Exceeded,
+    tracked: usize,
+    largest: Vec<(PathBuf, usize)>,
+    fallback_to_roots: bool,
+}
+
+/// Counts tracked paths per directory directly below each root, largest first.
+///
+/// Paths directly inside a root, other than such directories, count for the root.
+fn largest(roots: &[PathBuf], tracked: &[PathBuf]) -> Vec<(PathBuf, usize)> {
+    let placed: Vec<(&PathBuf, PathBuf, usize)> = tracked
+        .iter()
+        .filter_map(|path| {
+            let root = roots.iter().find(|root| path.starts_with(root))?;
+            let relative = path.strip_prefix(root).ok()?;
+            let child = match relative.components().next() {
+                Some(first) => root.join(first),
+                None => root.clone(),
+            };
+            Some((root, child, relative.components().count()))
+        })
+        .collect();
+    let directories: HashSet<&PathBuf> = placed
+        .iter()
+        .filter(|(_, _, depth)| *depth > 1)
+        .map(|(_, child, _)| child)
+        .collect();
+
+    let mut counts: HashMap<PathBuf, usize> = HashMap::new();
+    for (root, child, _) in &placed {
+        let key = if directories.contains(child) {
+            child
+        } else {
+            *root
+        };
+        *counts.entry(key.clone()).or_default() += 1;
+    }
+
+    let mut result: Vec<_> = counts.into_iter().filter(|(_, count)| *count > 1).collect();
+    result.sort_by(|(a_path, a_count), (b_path, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_path.cmp(b_path))
+    });
+    result.truncate(LARGEST);
+
+    result
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.exceeded {
+            Exceeded::Paths(max) => write!(
+                f,
+                "change-detection: tracked paths exceed the limit of {}",
+                max
+            )?,
+            Exceeded::ScanTime(max) => write!(
+                f,
+                "change-detection: scanning {} tracked paths exceeded the limit of {:?}",
+                self.tracked, max
+            )?,
+        }
+
+        if !self.largest.is_empty() {
+            let largest: Vec<String> = self
+                .largest
+                .iter()
+                .map(|(path, count)| format!("{} ({})", slash(path), count))
+                .collect();
+            write!(f, ", the largest directories are {}", largest.join(", "))?;
+        }
+
+        if self.fallback_to_roots {
+            write!(f, ", tracking the given paths only")
+        } else {
+            write!(f, ", exclude what doesn't affect the build")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{largest, Budget, Exceeded, Limits};
+    use std::{path::PathBuf, time::Duration};
+
+    #[test]
+    fn falls_back_only_when_enabled() {
+        let limits = Limits {
+            max_paths: Some(1),
+            ..Limits::default()
+        };
+
+        let budget = Budget::new(limits);
+        budget.visit(true);
+        budget.visit(false);
+        assert!(budget.exceeded().is_none());
+        budget.visit(true);
+        assert_eq!(budget.exceeded(), Some(Exceeded::Paths(1)));
+        assert!(!budget.falls_back());
+
+        let budget = Budget::new(Limits {
+            fallback_to_roots: true,
+            ..limits
+        });
+        budget.visit(true);
+        assert!(!budget.falls_back());
+        budget.visit(true);
+        assert!(budget.falls_back());
+        assert!(!budget.stopped());
+
+        let budget = Budget::new(Limits {
+            fallback_to_roots: true,
+            ..limits
+        })
+        .stopping_early(true);
+        budget.visit(true);
+        assert!(!budget.stopped());
+        budget.visit(true);
+        assert!(budget.stopped());
+    }
+
+    #[test]
+    fn limits_scan_time() {
+        let budget = Budget::new(Limits {
+            max_scan_time: Some(Duration::from_millis(0)),
+            ..Limits::default()
+        });
+        std::thread::sleep(Duration::from_millis(1));
+        budget.visit(false);
+
+        assert_eq!(
+            budget.exceeded(),
+            Some(Exceeded::ScanTime(Duration::from_millis(0)))
+        );
+    }
+
+    #[test]
+    fn reports_largest_directories() {
+        let roots = [PathBuf::from("."), PathBuf::from("build.rs")];
+        let tracked: Vec<PathBuf> = [
+            ".",
+            "./node_modules",
+            "./node_modules/a",
+            "./node_modules/a/index.js",
+            "./src",
+            "./src/lib.rs",
+            "./Cargo.toml",
+            "build.rs",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        assert_eq!(
+            largest(&roots, &tracked),
+            vec![
+                (PathBuf::from("./node_modules"), 3),
+                (PathBuf::from("."), 2),
+                (PathBuf::from("./src"), 2),
+            ]
+        );
+
+        let budget = Budget::new(Limits {
+            max_paths: Some(4),
+            fallback_to_roots: true,
+            ..Limits::default()
+        });
+        assert_eq!(
+            budget
+                .report(Exceeded::Paths(4), &roots, &tracked)
+                .to_string(),
+            "change-detection: tracked paths exceed the limit of 4, the largest directories are \
+             ./node_modules (3), . (2), ./src (2), tracking the given paths only"
+        );
+    }
+}
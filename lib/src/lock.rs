@@ -0,0 +1,277 @@
+use crate::hash::hash_bytes;
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io,
+    path::{Component, Path, PathBuf},
+};
+
+/// An advisory lock keyed by a path, shared between build scripts of different crates.
+///
+/// Cargo runs build scripts in parallel, so build scripts writing into the same directory
+/// should hold a lock on it. The lock file lives in the temporary directory, not next to the
+/// locked path, so taking a lock never changes tracked paths. The lock is released on drop.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::PathLock;
+/// let _lock = PathLock::acquire("web/dist").unwrap();
+///
+/// // build web/dist
+/// ```
+#[derive(Debug)]
+pub struct PathLock {
+    file: File,
+}
+
+impl PathLock {
+    /// Blocks until the lock on `path` is acquired.
+    pub fn acquire<P: AsRef<Path>>(path: P) -> io::Result<PathLock> {
+        let file = open(path.as_ref())?;
+        sys::lock(&file, true)?;
+
+        Ok(PathLock { file })
+    }
+
+    /// Acquires the lock on `path` if it isn't held by anybody else.
+    pub fn try_acquire<P: AsRef<Path>>(path: P) -> io::Result<Option<PathLock>> {
+        let file = open(path.as_ref())?;
+        if sys::lock(&file, false)? {
+            Ok(Some(PathLock { file }))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl Drop for PathLock {
+    fn drop(&mut self) {
+        let _ = sys::unlock(&self.file);
+    }
+}
+
+/// Advisory file locks, `flock` on Unix and `LockFileEx` on Windows.
+///
+/// `lock` returns `false` if `wait` is `false` and the lock is held by somebody else.
+#[cfg(unix)]
+mod sys {
+    use std::{fs::File, io, os::unix::io::AsRawFd};
+
+    pub(super) fn lock(file: &File, wait: bool) -> io::Result<bool> {
+        let operation = if wait {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_EX | libc::LOCK_NB
+        };
+        loop {
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+                return Ok(true);
+            }
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EWOULDBLOCK) => return Ok(false),
+                Some(libc::EINTR) => continue,
+                _ => return Err(err),
+            }
+        }
+    }
+
+    pub(super) fn unlock(file: &File) -> io::Result<()> {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::{
+        ffi::c_void,
+        fs::File,
+        io,
+        os::windows::io::{AsRawHandle, RawHandle},
+    };
+
+    const LOCKFILE_FAIL_IMMEDIATELY: u32 = 0x1;
+    const LOCKFILE_EXCLUSIVE_LOCK: u32 = 0x2;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    #[repr(C)]
+    struct Overlapped {
+        internal: usize,
+        internal_high: usize,
+        offset: u32,
+        offset_high: u32,
+        event: *mut c_void,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn LockFileEx(
+            file: RawHandle,
+            flags: u32,
+            reserved: u32,
+            bytes_low: u32,
+            bytes_high: u32,
+            overlapped: *mut Overlapped,
+        ) -> i32;
+        fn UnlockFileEx(
+            file: RawHandle,
+            reserved: u32,
+            bytes_low: u32,
+            bytes_high: u32,
+            overlapped: *mut Overlapped,
+        ) -> i32;
+    }
+
+    fn overlapped() -> Overlapped {
+        Overlapped {
+            internal: 0,
+            internal_high: 0,
+            offset: 0,
+            offset_high: 0,
+            event: std::ptr::null_mut(),
+        }
+    }
+
+    pub(super) fn lock(file: &File, wait: bool) -> io::Result<bool> {
+        let mut flags = LOCKFILE_EXCLUSIVE_LOCK;
+        if !wait {
+            flags |= LOCKFILE_FAIL_IMMEDIATELY;
+        }
+        let mut overlapped = overlapped();
+        let locked = unsafe {
+            LockFileEx(
+                file.as_raw_handle(),
+                flags,
+                0,
+                u32::MAX,
+                u32::MAX,
+                &mut overlapped,
+            )
+        };
+        if locked != 0 {
+            return Ok(true);
+        }
+
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(ERROR_LOCK_VIOLATION) => Ok(false),
+            _ => Err(err),
+        }
+    }
+
+    pub(super) fn unlock(file: &File) -> io::Result<()> {
+        let mut overlapped = overlapped();
+        let unlocked =
+            unsafe { UnlockFileEx(file.as_raw_handle(), 0, u32::MAX, u32::MAX, &mut overlapped) };
+        if unlocked != 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use std::{fs::File, io};
+
+    pub(super) fn lock(_file: &File, _wait: bool) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "file locks are not supported on this platform",
+        ))
+    }
+
+    pub(super) fn unlock(_file: &File) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn open(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_dir()?.join(lock_name(&absolute(path)?)))
+}
+
+/// Returns a manifest path next to the lock files keyed by `paths`, so build scripts of
+/// different crates holding locks on the same paths share what they built.
+pub(crate) fn shared_manifest(paths: &[PathBuf]) -> io::Result<PathBuf> {
+    let mut key = String::new();
+    for path in paths {
+        key.push_str(&absolute(path)?.to_string_lossy());
+        key.push('\n');
+    }
+
+    Ok(lock_dir()?.join(format!("{:016x}.manifest", hash_bytes(key.as_bytes()))))
+}
+
+fn lock_dir() -> io::Result<PathBuf> {
+    let dir = env::temp_dir().join("change-detection-locks");
+    fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+/// Makes `path` absolute without requiring it to exist, so every crate gets the same key.
+pub(crate) fn absolute(path: &Path) -> io::Result<PathBuf> {
+    let path = env::current_dir()?.join(path);
+
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+
+    Ok(result)
+}
+
+fn lock_name(path: &Path) -> String {
+    format!(
+        "{:016x}.lock",
+        hash_bytes(path.to_string_lossy().as_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{absolute, PathLock};
+    use crate::test_utils::TempDir;
+    use std::env;
+
+    #[test]
+    fn locks_are_exclusive_per_path() {
+        let tmp = TempDir::new("lock");
+        let dist = tmp.path().join("web/dist");
+
+        let lock = PathLock::acquire(&dist).unwrap();
+        assert!(PathLock::try_acquire(tmp.path().join("web/./src/../dist"))
+            .unwrap()
+            .is_none());
+        assert!(PathLock::try_acquire(tmp.path().join("web/src"))
+            .unwrap()
+            .is_some());
+
+        drop(lock);
+        assert!(PathLock::try_acquire(&dist).unwrap().is_some());
+    }
+
+    #[test]
+    fn relative_paths_are_absolute() {
+        assert_eq!(
+            absolute("./web/dist".as_ref()).unwrap(),
+            env::current_dir().unwrap().join("web/dist")
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! Debug logging of traversal and filter decisions.
+
+use std::{
+    env,
+    ffi::OsString,
+    fs::{self, File},
+    io::Write,
+    path::PathBuf,
+    sync::{Mutex, OnceLock},
+};
+
+/// Enables logging, `debug` logs roots and totals, `trace` logs every visited path as well.
+pub(crate) const LOG_ENV: &str = "CHANGE_DETECTION_LOG";
+
+/// Writes the log to a file with this name in `OUT_DIR` instead of stderr.
+pub(crate) const LOG_FILE_ENV: &str = "CHANGE_DETECTION_LOG_FILE";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Level {
+    Debug,
+    Trace,
+}
+
+struct Logger {
+    level: Option<Level>,
+    file: Option<Mutex<File>>,
+}
+
+impl Logger {
+    /// Configures a logger from environment variables looked up with `var`.
+    fn new(var: impl Fn(&str) -> Option<OsString>) -> Logger {
+        let level = match var(LOG_ENV).as_ref().and_then(|level| level.to_str()) {
+            Some("debug") => Some(Level::Debug),
+            Some("trace") => Some(Level::Trace),
+            _ => None,
+        };
+        let file = level
+            .and_then(|_| {
+                let name = var(LOG_FILE_ENV).filter(|name| !name.is_empty())?;
+                let path = PathBuf::from(var("OUT_DIR")?).join(name);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent).ok()?;
+                }
+                File::create(path).ok()
+            })
+            .map(Mutex::new);
+
+        Logger { level, file }
+    }
+
+    fn logs(&self, level: Level) -> bool {
+        self.level.is_some_and(|enabled| level <= enabled)
+    }
+
+    fn write(&self, level: Level, message: &str) {
+        if !self.logs(level) {
+            return;
+        }
+
+        let level = match level {
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+        let line = format!("change-detection: [{}] {}\n", level, message);
+        match &self.file {
+            Some(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = file.write_all(line.as_bytes());
+                }
+            }
+            None => eprint!("{}", line),
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+fn logger() -> &'static Logger {
+    LOGGER.get_or_init(|| Logger::new(|name| env::var_os(name)))
+}
+
+fn enabled(level: Level) -> bool {
+    #[cfg(feature = "log")]
+    {
+        let log_level = match level {
+            Level::Debug => log::Level::Debug,
+            Level::Trace => log::Level::Trace,
+        };
+        if log::log_enabled!(target: "change_detection", log_level) {
+            return true;
+        }
+    }
+
+    logger().logs(level)
+}
+
+fn write(level: Level, message: &str) {
+    #[cfg(feature = "log")]
+    match level {
+        Level::Debug => log::debug!(target: "change_detection", "{}", message),
+        Level::Trace => log::trace!(target: "change_detection", "{}", message),
+    }
+
+    logger().write(level, message);
+}
+
+/// Logs the message built by `message` if debug logging is enabled.
+pub(crate) fn debug<F: FnOnce() -> String>(message: F) {
+    if enabled(Level::Debug) {
+        write(Level::Debug, &message());
+    }
+}
+
+/// Logs the message built by `message` if trace logging is enabled.
+pub(crate) fn trace<F: FnOnce() -> String>(message: F) {
+    if enabled(Level::Trace) {
+        write(Level::Trace, &message());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Level, Logger, LOG_ENV, LOG_FILE_ENV};
+    use crate::test_utils::TempDir;
+    use std::{ffi::OsString, fs};
+
+    fn logger(vars: &[(&str, OsString)]) -> Logger {
+        Logger::new(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.clone())
+        })
+    }
+
+    #[test]
+    fn parses_levels() {
+        assert_eq!(logger(&[]).level, None);
+        assert_eq!(
+            logger(&[(LOG_ENV, "debug".into())]).level,
+            Some(Level::Debug)
+        );
+        assert_eq!(
+            logger(&[(LOG_ENV, "trace".into())]).level,
+            Some(Level::Trace)
+        );
+        assert_eq!(logger(&[(LOG_ENV, "verbose".into())]).level, None);
+    }
+
+    #[test]
+    fn trace_includes_debug() {
+        let debug = logger(&[(LOG_ENV, "debug".into())]);
+        assert!(debug.logs(Level::Debug));
+        assert!(!debug.logs(Level::Trace));
+
+        let trace = logger(&[(LOG_ENV, "trace".into())]);
+        assert!(trace.logs(Level::Debug));
+        assert!(trace.logs(Level::Trace));
+
+        assert!(!logger(&[]).logs(Level::Debug));
+    }
+
+    #[test]
+    fn writes_to_a_file_in_out_dir() {
+        let tmp = TempDir::new("log-file");
+        let vars = [
+            (LOG_ENV, OsString::from("debug")),
+            (LOG_FILE_ENV, OsString::from("logs/change-detection.log")),
+            ("OUT_DIR", tmp.path().into()),
+        ];
+
+        let debug = logger(&vars);
+        debug.write(Level::Debug, "collecting static");
+        debug.write(Level::Trace, "tracked static/index.html");
+
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("logs/change-detection.log")).unwrap(),
+            "change-detection: [debug] collecting static\n"
+        );
+
+        // Without a log level nothing is written, not even an empty file.
+        let tmp = TempDir::new("log-file-disabled");
+        logger(&[
+            (LOG_FILE_ENV, OsString::from("change-detection.log")),
+            ("OUT_DIR", tmp.path().into()),
+        ]);
+        assert!(!tmp.path().join("change-detection.log").exists());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::hash::{hash_bytes, hash_file};
+use path_slash::PathExt;
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// A snapshot of tracked paths and environment variables persisted between build script runs.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Manifest {
+    files: BTreeMap<String, Stamp>,
+    env: BTreeMap<String, Option<u64>>,
+}
+
+/// A modification stamp of a single tracked path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Stamp {
+    modified: u128,
+    len: u64,
+    hash: Option<u64>,
+}
+
+/// A single difference between two manifests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Difference {
+    Added(Subject),
+    Removed(Subject),
+    Modified(Subject),
+    /// Modification time changed, but the content hash did not.
+    Touched(Subject),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Subject {
+    File(String),
+    Env(String),
+}
+
+impl Manifest {
+    /// Takes a snapshot of `paths` and environment variables `env`.
+    ///
+    /// Paths which don't exist are left out. File contents are hashed only if `hash` is `true`.
+    pub(crate) fn new(paths: &[PathBuf], env: &[String], hash: bool) -> io::Result<Manifest> {
+        let mut files = BTreeMap::new();
+
+        for path in paths {
+            let key = path.to_slash().expect("can't convert path to utf-8 string");
+            match Stamp::of(path, hash) {
+                Ok(stamp) => {
+                    files.insert(key, stamp);
+                }
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let env = env
+            .iter()
+            .map(|name| {
+                let value =
+                    env::var_os(name).map(|value| hash_bytes(value.to_string_lossy().as_bytes()));
+                (name.clone(), value)
+            })
+            .collect();
+
+        Ok(Manifest { files, env })
+    }
+
+    /// Reads a manifest previously written with [`Manifest::write`].
+    ///
+    /// Returns `None` if there is no manifest at `path` yet or it can't be parsed,
+    /// for example because it was written by a different version of this crate.
+    pub(crate) fn read(path: &Path) -> io::Result<Option<Manifest>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        Ok(Manifest::parse(&content))
+    }
+
+    fn parse(content: &str) -> Option<Manifest> {
+        let mut manifest = Manifest::default();
+
+        for line in content.lines() {
+            let mut parts = line.splitn(5, '\t');
+            match parts.next()? {
+                "f" => {
+                    let stamp = Stamp {
+                        modified: parts.next()?.parse().ok()?,
+                        len: parts.next()?.parse().ok()?,
+                        hash: parse_hash(parts.next()?)?,
+                    };
+                    manifest.files.insert(parts.next()?.to_owned(), stamp);
+                }
+                "e" => {
+                    let value = parse_hash(parts.next()?)?;
+                    manifest.env.insert(parts.next()?.to_owned(), value);
+                }
+                _ => return None,
+            }
+        }
+
+        Some(manifest)
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut content = String::new();
+        for (key, stamp) in &self.files {
+            content.push_str(&format!(
+                "f\t{}\t{}\t{}\t{}\n",
+                stamp.modified,
+                stamp.len,
+                format_hash(stamp.hash),
+                key
+            ));
+        }
+        for (name, value) in &self.env {
+            content.push_str(&format!("e\t{}\t{}\n", format_hash(*value), name));
+        }
+
+        fs::write(path, content)
+    }
+
+    /// Lists everything that changed since the `previous` manifest.
+    pub(crate) fn diff(&self, previous: &Manifest) -> Vec<Difference> {
+        let mut result = vec![];
+
+        for (key, stamp) in &self.files {
+            let subject = || Subject::File(key.clone());
+            match previous.files.get(key) {
+                None => result.push(Difference::Added(subject())),
+                Some(old) if old == stamp => {}
+                Some(old) => match (old.hash, stamp.hash) {
+                    (Some(old_hash), Some(hash)) if old_hash == hash && old.len == stamp.len => {
+                        result.push(Difference::Touched(subject()))
+                    }
+                    _ => result.push(Difference::Modified(subject())),
+                },
+            }
+        }
+        for key in previous.files.keys() {
+            if !self.files.contains_key(key) {
+                result.push(Difference::Removed(Subject::File(key.clone())));
+            }
+        }
+
+        for (name, value) in &self.env {
+            let subject = || Subject::Env(name.clone());
+            match (previous.env.get(name), value) {
+                (None, _) | (Some(None), Some(_)) => result.push(Difference::Added(subject())),
+                (Some(Some(_)), None) => result.push(Difference::Removed(subject())),
+                (Some(old), value) if old != value => result.push(Difference::Modified(subject())),
+                _ => {}
+            }
+        }
+        for name in previous.env.keys() {
+            if !self.env.contains_key(name) {
+                result.push(Difference::Removed(Subject::Env(name.clone())));
+            }
+        }
+
+        result
+    }
+}
+
+impl Stamp {
+    fn of(path: &Path, hash: bool) -> io::Result<Stamp> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos());
+        let hash = if hash && metadata.is_file() {
+            Some(hash_file(path)?)
+        } else {
+            None
+        };
+
+        Ok(Stamp {
+            modified,
+            len: metadata.len(),
+            hash,
+        })
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Added(Subject::File(path)) => write!(f, "file {} was added", path),
+            Difference::Removed(Subject::File(path)) => write!(f, "file {} disappeared", path),
+            Difference::Modified(Subject::File(path)) => write!(f, "file {} was modified", path),
+            Difference::Touched(Subject::File(path)) => {
+                write!(f, "file {} was touched, its content is unchanged", path)
+            }
+            Difference::Added(Subject::Env(name)) => {
+                write!(f, "environment variable {} was set", name)
+            }
+            Difference::Removed(Subject::Env(name)) => {
+                write!(f, "environment variable {} was unset", name)
+            }
+            Difference::Modified(Subject::Env(name)) | Difference::Touched(Subject::Env(name)) => {
+                write!(f, "environment variable {} changed", name)
+            }
+        }
+    }
+}
+
+fn format_hash(hash: Option<u64>) -> String {
+    hash.map_or_else(|| "-".to_owned(), |hash| format!("{:016x}", hash))
+}
+
+fn parse_hash(s: &str) -> Option<Option<u64>> {
+    if s == "-" {
+        Some(None)
+    } else {
+        u64::from_str_radix(s, 16).ok().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Difference, Manifest, Subject};
+    use crate::test_utils::TempDir;
+    use std::fs;
+
+    #[test]
+    fn roundtrip_and_diff() {
+        let tmp = TempDir::new("manifest");
+        let a = tmp.path().join("a");
+        let b = tmp.path().join("b");
+        fs::write(&a, "a").unwrap();
+        fs::write(&b, "b").unwrap();
+
+        let manifest_path = tmp.path().join("manifest");
+        let previous = Manifest::new(
+            &[a.clone(), b.clone()],
+            &["CHANGE_DETECTION_TEST_UNSET".into()],
+            true,
+        )
+        .unwrap();
+        previous.write(&manifest_path).unwrap();
+        let previous = Manifest::read(&manifest_path).unwrap().unwrap();
+
+        fs::write(&a, "aa").unwrap();
+        let c = tmp.path().join("c");
+        fs::write(&c, "c").unwrap();
+
+        let current = Manifest::new(&[a.clone(), c.clone()], &[], true).unwrap();
+        let slash = |p: &std::path::Path| path_slash::PathExt::to_slash(p).unwrap();
+
+        assert_eq!(
+            current.diff(&previous),
+            vec![
+                Difference::Modified(Subject::File(slash(&a))),
+                Difference::Added(Subject::File(slash(&c))),
+                Difference::Removed(Subject::File(slash(&b))),
+                Difference::Removed(Subject::Env("CHANGE_DETECTION_TEST_UNSET".into())),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_manifest_is_ignored() {
+        assert_eq!(Manifest::parse("garbage"), None);
+    }
+}
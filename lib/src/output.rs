@@ -0,0 +1,119 @@
+use crate::sink::slash;
+use std::{
+    fmt,
+    path::{Component, Path, PathBuf},
+};
+
+/// A declared output which is, contains or is inside of tracked inputs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Overlap {
+    output: PathBuf,
+    /// Tracked inputs inside of the output.
+    inside: Vec<PathBuf>,
+    /// Tracked directories containing the output, including the output itself.
+    containing: Vec<PathBuf>,
+}
+
+/// Finds outputs overlapping `tracked` inputs.
+pub(crate) fn overlaps(outputs: &[PathBuf], tracked: &[PathBuf]) -> Vec<Overlap> {
+    outputs
+        .iter()
+        .filter_map(|output| {
+            let normalized = normalize(output);
+            let mut inside = vec![];
+            let mut containing = vec![];
+
+            for path in tracked {
+                let path_normalized = normalize(path);
+                if normalized.starts_with(&path_normalized) {
+                    containing.push(path.clone());
+                } else if path_normalized.starts_with(&normalized) {
+                    inside.push(path.clone());
+                }
+            }
+
+            if inside.is_empty() && containing.is_empty() {
+                None
+            } else {
+                Some(Overlap {
+                    output: output.clone(),
+                    inside,
+                    containing,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Removes `.` components, so `./web/dist` and `web/dist` compare equal.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+impl fmt::Display for Overlap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut tracked = self.containing.iter().chain(&self.inside);
+        write!(
+            f,
+            "change-detection: output {} overlaps tracked input {}",
+            slash(&self.output),
+            slash(tracked.next().expect("overlap without tracked inputs"))
+        )?;
+        let more = tracked.count();
+        if more > 0 {
+            write!(f, " and {} more", more)?;
+        }
+
+        write!(
+            f,
+            ", exclude it with `ChangeDetection::exclude(|path: &Path| path.starts_with({:?})",
+            slash(&self.output)
+        )?;
+        for path in &self.containing {
+            if normalize(path) != normalize(&self.output) {
+                write!(f, " || path == Path::new({:?})", slash(path))?;
+            }
+        }
+        write!(f, ")`")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::overlaps;
+    use std::path::PathBuf;
+
+    #[test]
+    fn suggests_excludes() {
+        let tracked: Vec<PathBuf> = ["./web", "web/src", "web/dist", "web/dist/index.js"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+
+        let result: Vec<String> = overlaps(
+            &[
+                PathBuf::from("web/dist"),
+                PathBuf::from("web/src/generated.rs"),
+                PathBuf::from("target"),
+            ],
+            &tracked,
+        )
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                "change-detection: output web/dist overlaps tracked input ./web and 2 more, \
+                 exclude it with `ChangeDetection::exclude(|path: &Path| path.starts_with(\"web/dist\") \
+                 || path == Path::new(\"./web\"))`",
+                "change-detection: output web/src/generated.rs overlaps tracked input ./web and 1 more, \
+                 exclude it with `ChangeDetection::exclude(|path: &Path| path.starts_with(\"web/src/generated.rs\") \
+                 || path == Path::new(\"./web\") || path == Path::new(\"web/src\"))`",
+            ]
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::decision::Hidden;
+#[cfg(feature = "ignore-files")]
+use crate::ignore_files::{self, IgnoreFiles, IgnoreStack};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Version control metadata directories, pruned wherever they appear below a tracked path.
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn", ".jj"];
+
+/// Directories excluded by default and paths hidden by ignore files, which are never walked.
+///
+/// Build outputs and version control metadata change on every build or commit,
+/// tracking them makes cargo rerun the build script over and over.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Prune {
+    /// Canonical paths of the target directory and `OUT_DIR`.
+    dirs: Vec<PathBuf>,
+    vcs: bool,
+    #[cfg(feature = "ignore-files")]
+    pub(crate) ignore_files: IgnoreFiles,
+}
+
+impl Prune {
+    /// Returns the default excludes, or nothing if `enabled` is `false`.
+    pub(crate) fn new(enabled: bool) -> Prune {
+        if !enabled {
+            return Prune::default();
+        }
+
+        let out_dir = env::var_os("OUT_DIR").map(PathBuf::from);
+        let target_dir = env::var_os("CARGO_TARGET_DIR")
+            .map(PathBuf::from)
+            .or_else(|| out_dir.as_deref().and_then(target_dir));
+
+        Prune {
+            dirs: target_dir
+                .into_iter()
+                .chain(out_dir)
+                .filter_map(|dir| fs::canonicalize(dir).ok())
+                .collect(),
+            vcs: true,
+            #[cfg(feature = "ignore-files")]
+            ignore_files: IgnoreFiles::default(),
+        }
+    }
+
+    /// Returns `true` if `dir` contains a pruned directory, which cargo would scan
+    /// when `dir` is tracked as a whole.
+    pub(crate) fn contained_in(&self, dir: &Path) -> bool {
+        !self.below(dir).dirs.is_empty()
+            || (self.vcs && VCS_DIRS.iter().any(|vcs| dir.join(vcs).exists()))
+    }
+
+    /// Prunes `dir` as if it was the target directory.
+    #[cfg(test)]
+    pub(crate) fn with_dir(mut self, dir: &Path) -> Prune {
+        self.dirs.push(fs::canonicalize(dir).unwrap());
+        self
+    }
+
+    /// Returns the pruned directories below `root`, in the form the traversal reaches them.
+    pub(crate) fn below(&self, root: &Path) -> Pruned {
+        let dirs = match fs::canonicalize(root) {
+            Ok(canonical) => self
+                .dirs
+                .iter()
+                .filter_map(|dir| dir.strip_prefix(&canonical).ok())
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .map(|relative| root.join(relative))
+                .collect(),
+            Err(_) => vec![],
+        };
+
+        Pruned {
+            dirs,
+            vcs: self.vcs,
+            #[cfg(feature = "ignore-files")]
+            ignores: if self.ignore_files.is_empty() {
+                None
+            } else {
+                Some(IgnoreStack::new(&self.ignore_files, root))
+            },
+            #[cfg(feature = "ignore-files")]
+            ignore_files: self.ignore_files.clone(),
+        }
+    }
+}
+
+/// Directories pruned below one tracked path.
+#[derive(Debug)]
+pub(crate) struct Pruned {
+    dirs: Vec<PathBuf>,
+    vcs: bool,
+    #[cfg(feature = "ignore-files")]
+    ignores: Option<IgnoreStack>,
+    #[cfg(feature = "ignore-files")]
+    ignore_files: IgnoreFiles,
+}
+
+impl Pruned {
+    /// Returns `true` if the traversal neither tracks nor walks `path`.
+    pub(crate) fn prunes(&self, path: &Path) -> bool {
+        #[cfg(feature = "ignore-files")]
+        {
+            if let Some(ignores) = &self.ignores {
+                if ignores.ignores(path, path.is_dir()) {
+                    return true;
+                }
+            }
+        }
+
+        self.excludes_by_default(path)
+    }
+
+    /// Reads ignore files of `dir` before walking it.
+    #[cfg(feature = "ignore-files")]
+    pub(crate) fn enter(&mut self, dir: &Path) {
+        if let Some(ignores) = &mut self.ignores {
+            ignores.enter(dir);
+        }
+    }
+
+    /// Forgets ignore files of the directory entered last.
+    #[cfg(feature = "ignore-files")]
+    pub(crate) fn leave(&mut self) {
+        if let Some(ignores) = &mut self.ignores {
+            ignores.leave();
+        }
+    }
+
+    /// Adds ignore files read during the traversal to `tracked`, editing them changes what is tracked.
+    #[cfg(feature = "ignore-files")]
+    pub(crate) fn with_ignore_files(&self, mut tracked: Vec<PathBuf>) -> Vec<PathBuf> {
+        if let Some(ignores) = &self.ignores {
+            for file in ignores.read() {
+                if !tracked.contains(file) {
+                    tracked.push(file.clone());
+                }
+            }
+        }
+
+        tracked
+    }
+
+    /// Returns why the traversal of `root` skips `path` or one of its ancestors.
+    pub(crate) fn hidden(&self, root: &Path, path: &Path) -> Option<Hidden> {
+        let relative = path.strip_prefix(root).ok()?;
+
+        let mut ancestor = root.to_path_buf();
+        for component in relative.components() {
+            ancestor.push(component);
+            if self.excludes_by_default(&ancestor) {
+                return Some(Hidden::Pruned(ancestor));
+            }
+        }
+
+        #[cfg(feature = "ignore-files")]
+        {
+            if let Some(ignored) = ignore_files::ignored_ancestor(&self.ignore_files, root, path) {
+                return Some(Hidden::Ignored(ignored));
+            }
+        }
+
+        None
+    }
+
+    /// Returns `true` if `path` is the target directory, `OUT_DIR` or version control metadata.
+    fn excludes_by_default(&self, path: &Path) -> bool {
+        self.dirs.iter().any(|dir| dir == path)
+            || (self.vcs
+                && path
+                    .file_name()
+                    .is_some_and(|name| VCS_DIRS.iter().any(|vcs| name == *vcs)))
+    }
+}
+
+/// Detects the target directory from `out_dir`, which looks like
+/// `target/[triple/]profile/build/package-hash/out`.
+///
+/// Cargo marks the target directory with a `CACHEDIR.TAG` file.
+fn target_dir(out_dir: &Path) -> Option<PathBuf> {
+    out_dir
+        .ancestors()
+        .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+        .or_else(|| {
+            let parent = out_dir
+                .ancestors()
+                .find(|dir| dir.file_name().is_some_and(|name| name == "build"))?
+                .parent()?
+                .parent()?;
+            // Target triples like `x86_64-unknown-linux-gnu` have at least three parts.
+            let is_triple = parent
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().split('-').count() >= 3);
+            if is_triple {
+                parent.parent()
+            } else {
+                Some(parent)
+            }
+        })
+        .map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{target_dir, Prune};
+    use crate::decision::Hidden;
+    use crate::test_utils::TempDir;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn prunes_target_dir_and_vcs_metadata() {
+        let tmp = TempDir::new("prune");
+        let target = tmp.path().join("target");
+        fs::create_dir_all(&target).unwrap();
+
+        let mut prune = Prune::new(true);
+        prune.dirs.push(fs::canonicalize(&target).unwrap());
+        let pruned = prune.below(tmp.path());
+
+        assert!(pruned.prunes(&tmp.path().join("target")));
+        assert!(pruned.prunes(&tmp.path().join("web/.git")));
+        assert!(!pruned.prunes(&tmp.path().join("web/src")));
+        assert_eq!(
+            pruned.hidden(tmp.path(), &tmp.path().join(".git/refs/heads")),
+            Some(Hidden::Pruned(tmp.path().join(".git")))
+        );
+
+        assert!(!Prune::default().below(tmp.path()).prunes(&target));
+
+        assert!(prune.contained_in(tmp.path()));
+        assert!(!prune.contained_in(&target));
+    }
+
+    #[test]
+    fn detects_target_dir() {
+        assert_eq!(
+            target_dir(Path::new(
+                "/work/target/x86_64-unknown-linux-gnu/debug/build/web-0123/out"
+            )),
+            Some(Path::new("/work/target").into())
+        );
+        assert_eq!(
+            target_dir(Path::new("/work/target/release/build/web-0123/out")),
+            Some(Path::new("/work/target").into())
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use path_slash::PathExt;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// A destination of change detection instructions.
+///
+/// Implement it to send instructions somewhere else than the build script output,
+/// see [`ChangeDetectionBuilder::generate_into`](crate::ChangeDetectionBuilder::generate_into).
+///
+/// # Examples:
+///
+/// To count tracked paths:
+///
+/// ```
+/// # use change_detection::InstructionSink;
+/// # use std::{io, path::Path};
+/// #[derive(Default)]
+/// struct Counter(usize);
+///
+/// impl InstructionSink for Counter {
+///     fn rerun_if_changed(&mut self, _path: &Path) -> io::Result<()> {
+///         self.0 += 1;
+///         Ok(())
+///     }
+///
+///     fn rerun_if_env_changed(&mut self, _name: &str) -> io::Result<()> {
+///         Ok(())
+///     }
+///
+///     fn warning(&mut self, _message: &str) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait InstructionSink {
+    /// Reruns the build script if `path` changes.
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()>;
+
+    /// Reruns the build script if the environment variable `name` changes.
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()>;
+
+    /// Sets the environment variable `name` for the compilation of the crate.
+    ///
+    /// Does nothing by default.
+    fn rustc_env(&mut self, _name: &str, _value: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Enables the cfg `name` for the compilation of the crate.
+    ///
+    /// Does nothing by default.
+    fn rustc_cfg(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Declares the cfg `name` as expected.
+    ///
+    /// Does nothing by default.
+    fn rustc_check_cfg(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Shows a warning to the user.
+    fn warning(&mut self, message: &str) -> io::Result<()>;
+
+    /// Called once all instructions were written.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: InstructionSink + ?Sized> InstructionSink for &mut S {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        (**self).rerun_if_changed(path)
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        (**self).rerun_if_env_changed(name)
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        (**self).rustc_env(name, value)
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        (**self).rustc_cfg(name)
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        (**self).rustc_check_cfg(name)
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        (**self).warning(message)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+impl<S: InstructionSink + ?Sized> InstructionSink for Box<S> {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        (**self).rerun_if_changed(path)
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        (**self).rerun_if_env_changed(name)
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        (**self).rustc_env(name, value)
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        (**self).rustc_cfg(name)
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        (**self).rustc_check_cfg(name)
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        (**self).warning(message)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Prints `cargo:` instructions to stdout, where cargo reads them from a build script.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StdoutSink;
+
+impl InstructionSink for StdoutSink {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        CargoWriter(io::stdout()).rerun_if_changed(path)
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        CargoWriter(io::stdout()).rerun_if_env_changed(name)
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        CargoWriter(io::stdout()).rustc_env(name, value)
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        CargoWriter(io::stdout()).rustc_cfg(name)
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        CargoWriter(io::stdout()).rustc_check_cfg(name)
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        CargoWriter(io::stdout()).warning(message)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        CargoWriter(io::stdout()).flush()
+    }
+}
+
+/// A single change detection instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    RerunIfChanged(PathBuf),
+    RerunIfEnvChanged(String),
+    RustcEnv { name: String, value: String },
+    RustcCfg(String),
+    RustcCheckCfg(String),
+    Warning(String),
+}
+
+/// Keeps instructions in memory, useful for tests.
+///
+/// # Examples:
+///
+/// ```
+/// # use change_detection::{ChangeDetection, Instruction, MemorySink};
+/// # use std::path::PathBuf;
+/// let mut sink = MemorySink::default();
+/// ChangeDetection::path("Cargo.toml")
+///     .env("PROFILE")
+///     .generate_into(&mut sink)
+///     .unwrap();
+///
+/// assert_eq!(
+///     sink.instructions,
+///     vec![
+///         Instruction::RerunIfChanged(PathBuf::from("Cargo.toml")),
+///         Instruction::RerunIfEnvChanged("PROFILE".to_owned()),
+///     ]
+/// );
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemorySink {
+    pub instructions: Vec<Instruction>,
+}
+
+impl MemorySink {
+    /// Returns paths from `rerun-if-changed` instructions.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::RerunIfChanged(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns variables from `rerun-if-env-changed` instructions.
+    pub fn env(&self) -> Vec<String> {
+        self.instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::RerunIfEnvChanged(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Writes all kept instructions to `sink`.
+    pub fn replay(&self, sink: &mut dyn InstructionSink) -> io::Result<()> {
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::RerunIfChanged(path) => sink.rerun_if_changed(path)?,
+                Instruction::RerunIfEnvChanged(name) => sink.rerun_if_env_changed(name)?,
+                Instruction::RustcEnv { name, value } => sink.rustc_env(name, value)?,
+                Instruction::RustcCfg(name) => sink.rustc_cfg(name)?,
+                Instruction::RustcCheckCfg(name) => sink.rustc_check_cfg(name)?,
+                Instruction::Warning(message) => sink.warning(message)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl InstructionSink for MemorySink {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        self.instructions
+            .push(Instruction::RerunIfChanged(path.to_path_buf()));
+        Ok(())
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        self.instructions
+            .push(Instruction::RerunIfEnvChanged(name.to_owned()));
+        Ok(())
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.instructions.push(Instruction::RustcEnv {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        });
+        Ok(())
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        self.instructions
+            .push(Instruction::RustcCfg(name.to_owned()));
+        Ok(())
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        self.instructions
+            .push(Instruction::RustcCheckCfg(name.to_owned()));
+        Ok(())
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        self.instructions
+            .push(Instruction::Warning(message.to_owned()));
+        Ok(())
+    }
+}
+
+/// Writes `cargo:` instructions to a file.
+#[derive(Debug)]
+pub struct FileSink(CargoWriter<BufWriter<File>>);
+
+impl FileSink {
+    /// Creates or truncates the file at `path`.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<FileSink> {
+        Ok(FileSink(CargoWriter(BufWriter::new(File::create(path)?))))
+    }
+}
+
+impl InstructionSink for FileSink {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        self.0.rerun_if_changed(path)
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        self.0.rerun_if_env_changed(name)
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        self.0.rustc_env(name, value)
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        self.0.rustc_cfg(name)
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        self.0.rustc_check_cfg(name)
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        self.0.warning(message)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Writes `cargo:` instructions to any writer.
+#[derive(Debug)]
+pub(crate) struct CargoWriter<W>(pub(crate) W);
+
+impl<W: Write> InstructionSink for CargoWriter<W> {
+    fn rerun_if_changed(&mut self, path: &Path) -> io::Result<()> {
+        writeln!(self.0, "cargo:rerun-if-changed={}", slash(path))
+    }
+
+    fn rerun_if_env_changed(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.0, "cargo:rerun-if-env-changed={}", name)
+    }
+
+    fn rustc_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        writeln!(self.0, "cargo:rustc-env={}={}", name, value)
+    }
+
+    fn rustc_cfg(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.0, "cargo:rustc-cfg={}", name)
+    }
+
+    fn rustc_check_cfg(&mut self, name: &str) -> io::Result<()> {
+        writeln!(self.0, "cargo:rustc-check-cfg=cfg({})", name)
+    }
+
+    fn warning(&mut self, message: &str) -> io::Result<()> {
+        writeln!(self.0, "cargo:warning={}", message)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+pub(crate) fn slash(path: &Path) -> String {
+    path.to_slash().expect("can't convert path to utf-8 string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileSink, InstructionSink, MemorySink};
+    use crate::test_utils::TempDir;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn file_sink_writes_cargo_instructions() {
+        let tmp = TempDir::new("file-sink");
+        let path = tmp.path().join("instructions");
+
+        let mut memory = MemorySink::default();
+        memory.rerun_if_changed(Path::new("web/src")).unwrap();
+        memory.rerun_if_env_changed("PROFILE").unwrap();
+        memory.warning("web/src is empty").unwrap();
+
+        let mut sink = FileSink::create(&path).unwrap();
+        memory.replay(&mut sink).unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "\
+cargo:rerun-if-changed=web/src
+cargo:rerun-if-env-changed=PROFILE
+cargo:warning=web/src is empty
+"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+use crate::{
+    finish_generate,
+    group::file_name,
+    lock,
+    manifest::{Difference, Manifest},
+    sink::InstructionSink,
+    ChangeDetectionBuilder, MemorySink, PathLock, StdoutSink,
+};
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+/// An external build step which runs only when its inputs changed.
+///
+/// Created with [`ChangeDetectionBuilder::task`]. Inputs are the paths tracked by the builder,
+/// outputs are declared with [`ChangeDetectionBuilder::output`]. The command runs if an output
+/// is missing, an output is older than an input, or input contents changed since
+/// the last successful run. After a failed run, the command runs again.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::ChangeDetection;
+/// # use std::process::Command;
+/// let mut npm = Command::new("npm");
+/// npm.args(["run", "build"]).current_dir("web");
+///
+/// ChangeDetection::path("web/src")
+///     .path("web/package.json")
+///     .output("web/dist")
+///     .task("web", npm)
+///     .run()
+///     .unwrap();
+/// ```
+pub struct Task {
+    name: String,
+    inputs: ChangeDetectionBuilder,
+    command: Command,
+    lock: bool,
+}
+
+impl Task {
+    pub(crate) fn new(name: String, inputs: ChangeDetectionBuilder, command: Command) -> Task {
+        Task {
+            name,
+            inputs,
+            command,
+            lock: false,
+        }
+    }
+
+    /// Holds a [`PathLock`] on every output while checking and running the task.
+    ///
+    /// Build scripts of different crates building the same outputs then run one after another,
+    /// and the later one finds the outputs up to date. The task's state is kept next to the lock
+    /// instead of `OUT_DIR` then, keyed by the outputs, so the crates share it.
+    pub fn lock(mut self) -> Task {
+        self.lock = true;
+        self
+    }
+
+    /// Generates change detection instructions for the inputs and runs the command if needed.
+    ///
+    /// Returns `true` if the command was run. If it fails, its output is reported
+    /// with `cargo:warning` and an error is returned.
+    pub fn run(self) -> io::Result<bool> {
+        self.run_into(StdoutSink)
+    }
+
+    /// Same as [`Task::run`], but writes instructions and warnings to `sink`.
+    pub fn run_into<S>(self, sink: S) -> io::Result<bool>
+    where
+        S: InstructionSink,
+    {
+        let out_dir = env::var_os("OUT_DIR").map(PathBuf::from);
+        self.run_in(sink, out_dir.as_deref())
+    }
+
+    /// Runs the task, keeping its manifest in `out_dir` if given.
+    fn run_in<S>(self, mut sink: S, out_dir: Option<&Path>) -> io::Result<bool>
+    where
+        S: InstructionSink,
+    {
+        let Task {
+            name,
+            inputs,
+            mut command,
+            lock,
+        } = self;
+        let outputs = inputs.outputs.clone();
+
+        let mut generated = MemorySink::default();
+        inputs.generate_extended(&mut generated)?;
+        let paths = generated.paths();
+        finish_generate(generated, &mut sink)?;
+
+        let mut locked = outputs.clone();
+        locked.sort();
+        let _locks = if lock {
+            locked
+                .iter()
+                .map(PathLock::acquire)
+                .collect::<io::Result<Vec<_>>>()?
+        } else {
+            vec![]
+        };
+
+        // Crates sharing locked outputs share the manifest too, with inputs keyed by absolute paths,
+        // so whichever runs later finds what the other one built.
+        let (manifest_path, manifest) = if lock {
+            let absolute = paths
+                .iter()
+                .map(|path| lock::absolute(path))
+                .collect::<io::Result<Vec<_>>>()?;
+            (
+                Some(lock::shared_manifest(&locked)?),
+                Manifest::new(&absolute, &[], true)?,
+            )
+        } else {
+            let manifest_path = out_dir.map(|out_dir| {
+                out_dir
+                    .join("change-detection")
+                    .join("tasks")
+                    .join(file_name(&name))
+            });
+            (manifest_path, Manifest::new(&paths, &[], true)?)
+        };
+
+        // Without a manifest the last run didn't succeed, its outputs can't be trusted.
+        let contents_changed = match &manifest_path {
+            Some(manifest_path) => Manifest::read(manifest_path)?.is_none_or(|previous| {
+                manifest
+                    .diff(&previous)
+                    .iter()
+                    .any(|difference| !matches!(difference, Difference::Touched(_)))
+            }),
+            None => false,
+        };
+        if !contents_changed && is_up_to_date(&paths, &outputs)? {
+            return Ok(false);
+        }
+
+        // Outputs of a failing run are newer than the inputs, the missing manifest reruns it.
+        if let Some(manifest_path) = &manifest_path {
+            match fs::remove_file(manifest_path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let output = command.output()?;
+        if !output.status.success() {
+            for line in String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .chain(String::from_utf8_lossy(&output.stderr).lines())
+            {
+                sink.warning(line)?;
+            }
+            sink.flush()?;
+
+            return Err(io::Error::other(format!(
+                "task {} failed: {:?} exited with {}",
+                name, command, output.status
+            )));
+        }
+
+        if let Some(manifest_path) = &manifest_path {
+            manifest.write(manifest_path)?;
+        }
+
+        Ok(true)
+    }
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("name", &self.name)
+            .field("outputs", &self.inputs.outputs)
+            .field("command", &self.command)
+            .field("lock", &self.lock)
+            .finish()
+    }
+}
+
+/// Returns `true` if all `outputs` exist and none of them is older than any of `inputs`.
+///
+/// Inputs are compared by their own modification times, since everything inside tracked
+/// directories passing the filters is listed separately. Outputs are compared by the newest
+/// modification time of their content.
+fn is_up_to_date(inputs: &[PathBuf], outputs: &[PathBuf]) -> io::Result<bool> {
+    let mut oldest_output = None;
+    for output in outputs {
+        match newest(output)? {
+            Some(modified) => {
+                if oldest_output.is_none_or(|oldest| modified < oldest) {
+                    oldest_output = Some(modified);
+                }
+            }
+            None => return Ok(false),
+        }
+    }
+    let oldest_output = match oldest_output {
+        Some(oldest_output) => oldest_output,
+        None => return Ok(false),
+    };
+
+    for input in inputs {
+        let modified = match fs::metadata(input).and_then(|metadata| metadata.modified()) {
+            Ok(modified) => modified,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if modified > oldest_output {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Returns the newest modification time of `path` and everything inside, or `None` if it is missing.
+fn newest(path: &Path) -> io::Result<Option<SystemTime>> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+
+    let mut result = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            if let Some(modified) = newest(&entry?.path())? {
+                result = result.max(modified);
+            }
+        }
+    }
+
+    Ok(Some(result))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use crate::{test_utils::TempDir, ChangeDetection, Instruction, MemorySink};
+    use std::{fs, process::Command, thread, time::Duration};
+
+    fn shell(script: &str) -> Command {
+        let mut command = Command::new("sh");
+        command.args(["-c", script]);
+        command
+    }
+
+    #[test]
+    fn runs_only_when_stale() {
+        let tmp = TempDir::new("task");
+        let src = tmp.path().join("src");
+        let dist = tmp.path().join("dist");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+
+        let run = || {
+            let script = format!(
+                "mkdir -p '{0}' && cp '{1}/index.js' '{0}/index.js'",
+                dist.display(),
+                src.display()
+            );
+            ChangeDetection::path(&src)
+                .output(&dist)
+                .task("web", shell(&script))
+                .run_into(MemorySink::default())
+                .unwrap()
+        };
+
+        assert!(run());
+        assert!(!run());
+
+        thread::sleep(Duration::from_millis(20));
+        fs::write(src.join("index.js"), "let a = 2;").unwrap();
+        assert!(run());
+        assert_eq!(
+            fs::read_to_string(dist.join("index.js")).unwrap(),
+            "let a = 2;"
+        );
+
+        fs::remove_dir_all(&dist).unwrap();
+        assert!(run());
+    }
+
+    #[test]
+    fn reruns_after_a_failed_run() {
+        let tmp = TempDir::new("task-rerun");
+        let src = tmp.path().join("src");
+        let dist = tmp.path().join("dist");
+        let out_dir = tmp.path().join("out");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+
+        let run = |exit: i32| {
+            let script = format!(
+                "mkdir -p '{0}' && cp '{1}/index.js' '{0}/index.js' && exit {2}",
+                dist.display(),
+                src.display(),
+                exit
+            );
+            ChangeDetection::path(&src)
+                .output(&dist)
+                .task("web", shell(&script))
+                .run_in(MemorySink::default(), Some(&out_dir))
+        };
+
+        assert!(run(1).is_err());
+        assert!(run(0).unwrap());
+
+        thread::sleep(Duration::from_millis(20));
+        fs::write(src.join("index.js"), "let a = 2;").unwrap();
+        assert!(run(1).is_err());
+
+        // The failed run left outputs newer than the inputs behind.
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        fs::write(dist.join("index.js"), "let a = 2;").unwrap();
+        assert!(run(0).unwrap());
+        assert!(!run(0).unwrap());
+    }
+
+    #[test]
+    fn reports_failures_as_warnings() {
+        let tmp = TempDir::new("task-failure");
+
+        let mut sink = MemorySink::default();
+        let result = ChangeDetection::path(tmp.path())
+            .output(tmp.path().join("dist"))
+            .task(
+                "web",
+                shell("echo compiling; echo 'syntax error' >&2; exit 1"),
+            )
+            .run_into(&mut sink);
+
+        assert!(result.is_err());
+        assert_eq!(
+            &sink.instructions[sink.instructions.len() - 2..],
+            &[
+                Instruction::Warning("compiling".into()),
+                Instruction::Warning("syntax error".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn locked_tasks_run_one_after_another() {
+        let tmp = TempDir::new("task-lock");
+        let src = tmp.path().join("src");
+        let dist = tmp.path().join("dist");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+
+        let script = format!(
+            "sleep 0.2 && mkdir -p '{0}' && cp '{1}/index.js' '{0}/index.js'",
+            dist.display(),
+            src.display()
+        );
+        let runs: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|krate| {
+                let (src, dist, script) = (src.clone(), dist.clone(), script.clone());
+                let out_dir = tmp.path().join(krate).join("out");
+                thread::spawn(move || {
+                    ChangeDetection::path(&src)
+                        .output(&dist)
+                        .task("web", shell(&script))
+                        .lock()
+                        .run_in(MemorySink::default(), Some(&out_dir))
+                        .unwrap()
+                })
+            })
+            .collect();
+        let ran: Vec<bool> = runs.into_iter().map(|run| run.join().unwrap()).collect();
+
+        assert_eq!(ran.iter().filter(|ran| **ran).count(), 1);
+    }
+
+    #[test]
+    fn locked_tasks_share_state_between_crates() {
+        let tmp = TempDir::new("task-lock-shared");
+        let src = tmp.path().join("src");
+        let dist = tmp.path().join("dist");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+
+        let run = |krate: &str| {
+            let script = format!(
+                "mkdir -p '{0}' && cp '{1}/index.js' '{0}/index.js'",
+                dist.display(),
+                src.display()
+            );
+            ChangeDetection::path(&src)
+                .output(&dist)
+                .task("web", shell(&script))
+                .lock()
+                .run_in(MemorySink::default(), Some(&tmp.path().join(krate)))
+                .unwrap()
+        };
+
+        assert!(run("a"));
+        assert!(!run("b"));
+
+        thread::sleep(Duration::from_millis(20));
+        fs::write(src.join("index.js"), "let a = 2;").unwrap();
+        assert!(run("b"));
+        assert!(!run("a"));
+    }
+}
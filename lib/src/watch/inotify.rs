@@ -0,0 +1,362 @@
+use super::{Change, ChangeKind, Skip};
+use crate::existing_ancestor;
+use inotify::{EventMask, Inotify, WatchMask};
+use std::{
+    collections::HashMap,
+    fs, io,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// An inotify based backend.
+pub(super) struct Backend {
+    inotify: Inotify,
+    /// Watched directories by watch descriptor, with a flag whether new subdirectories are watched too.
+    dirs: HashMap<i32, (PathBuf, bool)>,
+    roots: Vec<PathBuf>,
+    /// Roots which aren't directories, watched through their nearest existing ancestor until they are.
+    pending: Vec<PathBuf>,
+    buffer: Vec<u8>,
+    waker: Waker,
+    skip: Skip,
+}
+
+/// Wakes up a [`Backend`] waiting for changes, from any thread.
+#[derive(Debug, Clone)]
+pub(super) struct Waker(Arc<OwnedFd>);
+
+impl Waker {
+    fn new() -> io::Result<Waker> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Waker(Arc::new(unsafe { OwnedFd::from_raw_fd(fd) })))
+    }
+
+    pub(super) fn wake(&self) {
+        let one: u64 = 1;
+        // Fails only if the counter is about to overflow, then a wake up is pending anyway.
+        unsafe {
+            libc::write(
+                self.0.as_raw_fd(),
+                &one as *const u64 as *const libc::c_void,
+                8,
+            )
+        };
+    }
+
+    /// Consumes pending wake ups, returns `true` if there were any.
+    fn reset(&self) -> bool {
+        let mut count: u64 = 0;
+        let read = unsafe {
+            libc::read(
+                self.0.as_raw_fd(),
+                &mut count as *mut u64 as *mut libc::c_void,
+                8,
+            )
+        };
+
+        read == 8
+    }
+}
+
+impl Backend {
+    pub(super) fn new(roots: &[PathBuf], skip: Skip) -> io::Result<Backend> {
+        let mut backend = Backend {
+            inotify: Inotify::init()?,
+            dirs: HashMap::new(),
+            roots: roots.to_vec(),
+            pending: roots.to_vec(),
+            buffer: vec![0; 64 * 1024],
+            waker: Waker::new()?,
+            skip,
+        };
+        backend.watch_pending(&mut vec![])?;
+
+        Ok(backend)
+    }
+
+    /// Watches pending roots which became directories, collecting their content into `found`,
+    /// and the nearest existing ancestor of the others, so their creation is noticed.
+    fn watch_pending(&mut self, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        for path in std::mem::take(&mut self.pending) {
+            if path.is_dir() {
+                match self.add_recursive(&path, found) {
+                    Ok(()) => continue,
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if let Some(ancestor) = existing_ancestor(&path) {
+                if !self.dirs.values().any(|(dir, _)| dir == ancestor) {
+                    self.add(ancestor, false)?;
+                }
+            }
+            self.pending.push(path);
+        }
+
+        Ok(())
+    }
+
+    fn add(&mut self, dir: &Path, recursive: bool) -> io::Result<()> {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::ATTRIB
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::DELETE_SELF
+            | WatchMask::MOVE_SELF;
+
+        let wd = self.inotify.watches().add(dir, mask)?;
+        self.dirs
+            .insert(wd.get_watch_descriptor_id(), (dir.to_path_buf(), recursive));
+
+        Ok(())
+    }
+
+    /// Watches `dir` with all its subdirectories, collecting their content into `found`.
+    ///
+    /// Skipped subdirectories are left out with their content.
+    fn add_recursive(&mut self, dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+        self.add(dir, true)?;
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() && (self.skip)(&entry.path()) {
+                continue;
+            }
+            found.push(entry.path());
+            if entry.file_type()?.is_dir() {
+                self.add_recursive(&entry.path(), found)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// Returns changes, waiting for them at most `timeout` or forever if it is `None`.
+    ///
+    /// Returns no changes right away when woken up with a [`Waker`].
+    pub(super) fn next(&mut self, timeout: Option<Duration>) -> io::Result<Vec<Change>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut new_dirs = vec![];
+        let mut created = false;
+        let mut overflowed = false;
+        let mut result = vec![];
+
+        {
+            let Backend {
+                inotify,
+                dirs,
+                roots,
+                pending,
+                buffer,
+                waker,
+                skip,
+            } = self;
+
+            let events = loop {
+                if waker.reset() {
+                    return Ok(vec![]);
+                }
+                match inotify.read_events(buffer) {
+                    Ok(events) => break events,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        if !wait(&[inotify.as_raw_fd(), waker.0.as_raw_fd()], deadline)? {
+                            return Ok(vec![]);
+                        }
+                    }
+                    Err(err) => return Err(err),
+                }
+            };
+
+            for event in events {
+                // The kernel dropped events, they don't belong to any watch.
+                if event.mask.contains(EventMask::Q_OVERFLOW) {
+                    overflowed = true;
+                    continue;
+                }
+                let (dir, recursive) = match dirs.get(&event.wd.get_watch_descriptor_id()) {
+                    Some(dir) => dir,
+                    None => continue,
+                };
+                let path = match event.name {
+                    Some(name) => dir.join(name),
+                    None => dir.clone(),
+                };
+
+                let kind = if event
+                    .mask
+                    .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+                {
+                    ChangeKind::Added
+                } else if event.mask.intersects(
+                    EventMask::DELETE
+                        | EventMask::MOVED_FROM
+                        | EventMask::DELETE_SELF
+                        | EventMask::MOVE_SELF,
+                ) {
+                    ChangeKind::Removed
+                } else {
+                    ChangeKind::Modified
+                };
+
+                if kind == ChangeKind::Added && event.mask.contains(EventMask::ISDIR) {
+                    if skip(&path) {
+                        continue;
+                    }
+                    if *recursive {
+                        new_dirs.push(path.clone());
+                    } else {
+                        created = true;
+                    }
+                }
+                // A removed root is watched through its ancestor again until it is recreated.
+                if event
+                    .mask
+                    .intersects(EventMask::DELETE_SELF | EventMask::MOVE_SELF)
+                    && roots.contains(&path)
+                    && !pending.contains(&path)
+                {
+                    pending.push(path.clone());
+                    created = true;
+                }
+                if event.mask.contains(EventMask::IGNORED) {
+                    dirs.remove(&event.wd.get_watch_descriptor_id());
+                    continue;
+                }
+
+                result.push(Change { path, kind });
+            }
+        }
+
+        if created && !self.pending.is_empty() {
+            let mut found = vec![];
+            self.watch_pending(&mut found)?;
+            result.extend(found.into_iter().map(|path| Change {
+                path,
+                kind: ChangeKind::Added,
+            }));
+        }
+
+        // Dropped events might have been about anything below the roots, so everything is reported
+        // and watches are added for directories created meanwhile.
+        if overflowed {
+            let mut found = vec![];
+            for root in self.roots.clone() {
+                if root.is_dir() && !self.pending.contains(&root) {
+                    match self.add_recursive(&root, &mut found) {
+                        Ok(()) => {}
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                        Err(err) => return Err(err),
+                    }
+                }
+                found.push(root);
+            }
+            self.watch_pending(&mut found)?;
+            result.extend(found.into_iter().map(|path| Change {
+                path,
+                kind: ChangeKind::Modified,
+            }));
+        }
+
+        // Files might have been created in a new directory before its watch was added.
+        for dir in new_dirs {
+            let mut found = vec![];
+            match self.add_recursive(&dir, &mut found) {
+                Ok(()) => {}
+                Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+
+            result.extend(found.into_iter().map(|path| Change {
+                path,
+                kind: ChangeKind::Added,
+            }));
+        }
+
+        Ok(result)
+    }
+}
+
+/// Waits until one of `fds` is readable, returns `false` if `deadline` passed first.
+fn wait(fds: &[RawFd], deadline: Option<Instant>) -> io::Result<bool> {
+    let mut fds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+
+    loop {
+        let timeout = match deadline {
+            // Rounded up, so the deadline has passed once poll times out.
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                (left + Duration::from_nanos(999_999))
+                    .as_millis()
+                    .min(libc::c_int::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+
+        match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) } {
+            0 if timeout == 0 => return Ok(false),
+            0 => continue,
+            ready if ready > 0 => return Ok(true),
+            _ => {
+                let err = io::Error::last_os_error();
+                if err.raw_os_error() != Some(libc::EINTR) {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::skip, Backend};
+    use crate::{prune::Prune, test_utils::TempDir};
+    use std::{fs, time::Duration};
+
+    #[test]
+    fn leaves_pruned_directories_unwatched() {
+        let tmp = TempDir::new("watch-pruned");
+        let root = tmp.path().join("crate");
+        for dir in [".git/objects", "target/debug", "src/bin"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        let prune = Prune::new(true).with_dir(&root.join("target"));
+
+        let roots = vec![root.clone()];
+        let mut backend = Backend::new(&roots, skip(&roots, &prune)).unwrap();
+        fs::create_dir_all(root.join("src/gen/.git")).unwrap();
+        backend.next(Some(Duration::from_millis(100))).unwrap();
+
+        let mut watched: Vec<_> = backend.dirs.values().map(|(dir, _)| dir.clone()).collect();
+        watched.sort();
+        assert_eq!(
+            watched,
+            vec![
+                root.clone(),
+                root.join("src"),
+                root.join("src/bin"),
+                root.join("src/gen"),
+            ]
+        );
+    }
+}
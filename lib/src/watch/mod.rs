@@ -0,0 +1,512 @@
+//! Watching tracked paths for changes.
+//!
+//! Uses inotify on Linux and polls modification times on other platforms.
+
+use crate::prune::{Prune, Pruned};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[cfg(target_os = "linux")]
+mod inotify;
+#[cfg(not(target_os = "linux"))]
+mod poll;
+
+#[cfg(target_os = "linux")]
+use self::inotify::{Backend, Waker};
+#[cfg(not(target_os = "linux"))]
+use self::poll::{Backend, Waker};
+
+/// Returns `true` for directories which are neither watched nor walked.
+type Skip = Box<dyn Fn(&Path) -> bool + Send>;
+
+/// A kind of change of a watched path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+/// A change of a watched path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+}
+
+/// Waits for changes of tracked paths.
+///
+/// A change is reported for a tracked path itself or for anything inside a tracked directory,
+/// the same way cargo checks `rerun-if-changed` paths, except for the target directory,
+/// `OUT_DIR` and version control metadata, which aren't watched. Bursts of changes are debounced
+/// and reported as a single batch. To apply include and exclude filters to changes,
+/// use [`ChangeDetectionBuilder::watch`](crate::ChangeDetectionBuilder::watch) instead.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::watch::Watcher;
+/// let mut watcher = Watcher::new(vec!["web/src", "web/package.json"]).unwrap();
+///
+/// loop {
+///     let changed = watcher.wait().unwrap();
+///     println!("changed: {:?}", changed);
+/// }
+/// ```
+pub struct Watcher {
+    tracked: Vec<PathBuf>,
+    events: Events,
+}
+
+impl Watcher {
+    /// Starts watching `tracked` paths.
+    ///
+    /// Paths which don't exist yet are reported once they are created.
+    pub fn new<I, P>(tracked: I) -> io::Result<Watcher>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let tracked: Vec<PathBuf> = tracked
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf())
+            .collect();
+
+        Ok(Watcher {
+            events: Events::new(&tracked, &Prune::new(true))?,
+            tracked,
+        })
+    }
+
+    /// Sets a quiet period to wait for after a change before reporting a batch.
+    ///
+    /// Defaults to 200 milliseconds.
+    pub fn debounce(mut self, debounce: Duration) -> Watcher {
+        self.events.debounce = debounce;
+        self
+    }
+
+    /// Returns `true` if a change of `path` is reported by this watcher.
+    pub fn is_tracked(&self, path: &Path) -> bool {
+        self.tracked.iter().any(|tracked| path.starts_with(tracked))
+    }
+
+    /// Blocks until tracked paths change and returns the changed paths, sorted.
+    pub fn wait(&mut self) -> io::Result<Vec<PathBuf>> {
+        loop {
+            let changed = self.batch(None)?;
+            if !changed.is_empty() {
+                return Ok(changed);
+            }
+        }
+    }
+
+    /// Waits at most `timeout` for tracked paths to change and returns the changed paths, sorted.
+    ///
+    /// Changes which happened since the watcher was created or last waited for are returned
+    /// right away, so a zero `timeout` collects them without blocking.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> io::Result<Vec<PathBuf>> {
+        self.batch(Some(timeout))
+    }
+
+    fn batch(&mut self, timeout: Option<Duration>) -> io::Result<Vec<PathBuf>> {
+        let Watcher { tracked, events } = self;
+        let is_tracked = |path: &Path| tracked.iter().any(|tracked| path.starts_with(tracked));
+
+        let changes = events.batch(timeout, &is_tracked)?;
+
+        Ok(changes.into_iter().map(|change| change.path).collect())
+    }
+}
+
+/// Delivers batches of changes of paths which pass the builder filters.
+///
+/// Returned by [`ChangeDetectionBuilder::watch`](crate::ChangeDetectionBuilder::watch).
+/// Changes are delivered to the callback while [`WatchHandle::run`] or [`WatchHandle::poll`]
+/// is called, so the handle is usually created and run on a dedicated thread, and stopped from
+/// other threads with a [`Stopper`]. The builder filters aren't required to be `Send`, so neither
+/// is the handle.
+/// Directories created after the watch started are watched as well, pruned directories
+/// like the target directory aren't.
+pub struct WatchHandle {
+    events: Events,
+    filter: Box<dyn Fn(&Path) -> bool>,
+    callback: Box<dyn FnMut(Vec<Change>)>,
+    stopped: Arc<AtomicBool>,
+}
+
+/// Stops a [`WatchHandle`] from any thread.
+#[derive(Debug, Clone)]
+pub struct Stopper {
+    stopped: Arc<AtomicBool>,
+    waker: Waker,
+}
+
+impl WatchHandle {
+    pub(crate) fn new(
+        roots: &[PathBuf],
+        prune: &Prune,
+        filter: Box<dyn Fn(&Path) -> bool>,
+        callback: Box<dyn FnMut(Vec<Change>)>,
+    ) -> io::Result<WatchHandle> {
+        Ok(WatchHandle {
+            events: Events::new(roots, prune)?,
+            filter,
+            callback,
+            stopped: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Sets a quiet period to wait for after a change before delivering a batch.
+    ///
+    /// Defaults to 200 milliseconds.
+    pub fn debounce(mut self, debounce: Duration) -> WatchHandle {
+        self.events.debounce = debounce;
+        self
+    }
+
+    /// Returns a [`Stopper`] which makes [`WatchHandle::run`] return.
+    pub fn stopper(&self) -> Stopper {
+        Stopper {
+            stopped: self.stopped.clone(),
+            waker: self.events.backend.waker(),
+        }
+    }
+
+    /// Delivers batches of changes until stopped.
+    pub fn run(mut self) -> io::Result<()> {
+        while !self.stopped.load(Ordering::SeqCst) {
+            self.deliver(None)?;
+        }
+
+        Ok(())
+    }
+
+    /// Waits at most `timeout` for changes and delivers them as a single batch.
+    ///
+    /// Returns `true` if a batch was delivered.
+    pub fn poll(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.deliver(Some(timeout))
+    }
+
+    /// Waits at most `timeout`, or until stopped if it is `None`, and delivers a batch.
+    fn deliver(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        let changes = self.events.batch(timeout, &*self.filter)?;
+        if changes.is_empty() {
+            return Ok(false);
+        }
+
+        (self.callback)(changes);
+
+        Ok(true)
+    }
+}
+
+impl Stopper {
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.waker.wake();
+    }
+}
+
+impl fmt::Debug for WatchHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WatchHandle")
+            .field("stopped", &self.stopped)
+            .finish()
+    }
+}
+
+/// Debounces raw backend events into batches.
+struct Events {
+    backend: Backend,
+    debounce: Duration,
+}
+
+impl Events {
+    fn new(roots: &[PathBuf], prune: &Prune) -> io::Result<Events> {
+        Ok(Events {
+            backend: Backend::new(roots, skip(roots, prune))?,
+            debounce: Duration::from_millis(200),
+        })
+    }
+
+    /// Waits at most `timeout`, or forever if it is `None`, for changes passing `filter`
+    /// and collects them until no changes arrive during the debounce period.
+    fn batch(
+        &mut self,
+        timeout: Option<Duration>,
+        filter: &dyn Fn(&Path) -> bool,
+    ) -> io::Result<Vec<Change>> {
+        let mut batch = BTreeMap::new();
+
+        let changes = self.backend.next(timeout)?;
+        if changes.is_empty() {
+            return Ok(vec![]);
+        }
+        merge(&mut batch, changes, filter);
+
+        loop {
+            let changes = self.backend.next(Some(self.debounce))?;
+            if changes.is_empty() {
+                break;
+            }
+            merge(&mut batch, changes, filter);
+        }
+
+        Ok(batch
+            .into_iter()
+            .map(|(path, kind)| Change { path, kind })
+            .collect())
+    }
+}
+
+/// Skips directories the traversal of every root containing them prunes.
+fn skip(roots: &[PathBuf], prune: &Prune) -> Skip {
+    let pruned: Vec<(PathBuf, Pruned)> = roots
+        .iter()
+        .map(|root| (root.clone(), prune.below(root)))
+        .collect();
+
+    Box::new(move |dir| {
+        let mut containing = pruned
+            .iter()
+            .filter(|(root, _)| dir.starts_with(root))
+            .peekable();
+
+        containing.peek().is_some()
+            && containing.all(|(root, pruned)| pruned.hidden(root, dir).is_some())
+    })
+}
+
+/// Merges consecutive changes of the same path, so each path is reported once per batch.
+fn merge(
+    batch: &mut BTreeMap<PathBuf, ChangeKind>,
+    changes: Vec<Change>,
+    filter: &dyn Fn(&Path) -> bool,
+) {
+    for Change { path, kind } in changes {
+        if !filter(&path) {
+            continue;
+        }
+
+        match (batch.get(&path).copied(), kind) {
+            (Some(ChangeKind::Added), ChangeKind::Removed) => {
+                batch.remove(&path);
+            }
+            (Some(ChangeKind::Added), _) => {}
+            (Some(ChangeKind::Removed), ChangeKind::Added) => {
+                batch.insert(path, ChangeKind::Modified);
+            }
+            _ => {
+                batch.insert(path, kind);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, ChangeKind, Watcher};
+    use crate::{test_utils::TempDir, ChangeDetection};
+    use std::{
+        fs,
+        path::Path,
+        sync::{mpsc, Arc, Mutex},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn reports_tracked_changes_only() {
+        let tmp = TempDir::new("watch");
+        let src = tmp.path().join("web/src");
+        let dist = tmp.path().join("web/dist");
+        fs::create_dir_all(src.join("imgs")).unwrap();
+        fs::create_dir_all(&dist).unwrap();
+
+        let mut watcher = Watcher::new(vec![&src])
+            .unwrap()
+            .debounce(Duration::from_millis(100));
+
+        let writer = {
+            let src = src.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                fs::write(dist.join("index.js"), "let a = 1;").unwrap();
+                fs::write(src.join("imgs/01.jpg"), "jpg").unwrap();
+            })
+        };
+
+        let changed = watcher.wait_timeout(Duration::from_secs(10)).unwrap();
+        writer.join().unwrap();
+
+        assert!(changed.contains(&src.join("imgs/01.jpg")));
+        assert!(changed.iter().all(|path| path.starts_with(&src)));
+    }
+
+    #[test]
+    fn follows_tracked_directories_created_later() {
+        let tmp = TempDir::new("watch-created");
+        let src = tmp.path().join("web/src");
+
+        let mut watcher = Watcher::new(vec![&src])
+            .unwrap()
+            .debounce(Duration::from_millis(100));
+
+        fs::create_dir_all(src.join("imgs")).unwrap();
+        fs::write(src.join("imgs/01.jpg"), "jpg").unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut changed = vec![];
+        while !changed.contains(&src.join("imgs/01.jpg")) {
+            assert!(
+                Instant::now() < deadline,
+                "missed changes, got {:?}",
+                changed
+            );
+            changed.extend(watcher.wait_timeout(Duration::from_millis(500)).unwrap());
+        }
+        assert!(changed.iter().all(|path| path.starts_with(&src)));
+
+        fs::write(src.join("imgs/02.jpg"), "jpg").unwrap();
+        let changed = watcher.wait_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(changed, vec![src.join("imgs/02.jpg")]);
+    }
+
+    #[test]
+    fn reports_changes_after_dropped_events() {
+        let tmp = TempDir::new("watch-overflow");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+
+        let mut watcher = Watcher::new(vec![&src])
+            .unwrap()
+            .debounce(Duration::from_millis(100));
+
+        // More files than inotify queues events for, unless the limit was raised a lot.
+        let count = fs::read_to_string("/proc/sys/fs/inotify/max_queued_events")
+            .ok()
+            .and_then(|max| max.trim().parse::<usize>().ok())
+            .map_or(1000, |max| max.min(50_000) + 1);
+        for i in 0..count {
+            fs::write(src.join(format!("{}.js", i)), "").unwrap();
+        }
+        let last = src.join(format!("{}.js", count - 1));
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut changed = vec![];
+        while !changed.contains(&last) {
+            assert!(Instant::now() < deadline, "missed {}", last.display());
+            changed.extend(watcher.wait_timeout(Duration::from_millis(500)).unwrap());
+        }
+    }
+
+    #[test]
+    fn builder_watch_applies_filters_and_follows_new_directories() {
+        let tmp = TempDir::new("builder-watch");
+        let web = tmp.path().join("web");
+        fs::create_dir_all(web.join("dist")).unwrap();
+        fs::write(web.join("index.js"), "let a = 1;").unwrap();
+
+        let batches = Arc::new(Mutex::new(vec![]));
+        let mut handle = {
+            let batches = batches.clone();
+            let dist = web.join("dist");
+            ChangeDetection::path_exclude(&web, move |path: &Path| path.starts_with(&dist))
+                .watch(move |changes| batches.lock().unwrap().push(changes))
+                .unwrap()
+                .debounce(Duration::from_millis(100))
+        };
+
+        fs::create_dir_all(web.join("imgs")).unwrap();
+        poll_until_delivered(&mut handle);
+
+        fs::write(web.join("imgs/01.jpg"), "jpg").unwrap();
+        fs::write(web.join("dist/index.js"), "let a = 1;").unwrap();
+        fs::remove_file(web.join("index.js")).unwrap();
+        poll_until_delivered(&mut handle);
+
+        let batches = batches.lock().unwrap();
+        assert!(batches[0].contains(&Change {
+            path: web.join("imgs"),
+            kind: ChangeKind::Added
+        }));
+        assert!(batches[1].contains(&Change {
+            path: web.join("imgs/01.jpg"),
+            kind: ChangeKind::Added
+        }));
+        assert!(batches[1].contains(&Change {
+            path: web.join("index.js"),
+            kind: ChangeKind::Removed
+        }));
+        assert!(batches[1]
+            .iter()
+            .all(|change| !change.path.starts_with(web.join("dist"))));
+    }
+
+    #[test]
+    fn handle_runs_on_another_thread() {
+        let tmp = TempDir::new("builder-watch-thread");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let (stoppers, stopper) = mpsc::channel();
+        let watching = {
+            let src = src.clone();
+            thread::spawn(move || {
+                let handle = ChangeDetection::path(&src)
+                    .watch(move |changes| sender.send(changes).unwrap())
+                    .unwrap()
+                    .debounce(Duration::from_millis(100));
+                stoppers.send(handle.stopper()).unwrap();
+                handle.run()
+            })
+        };
+        let stopper = stopper.recv().unwrap();
+
+        fs::write(src.join("index.js"), "let a = 1;").unwrap();
+        let changes = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(changes
+            .iter()
+            .any(|change| change.path == src.join("index.js")));
+
+        stopper.stop();
+        watching.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn stopper_wakes_up_an_idle_handle() {
+        let tmp = TempDir::new("builder-watch-stop");
+        let src = tmp.path().join("src");
+        fs::create_dir_all(&src).unwrap();
+
+        let (stoppers, stopper) = mpsc::channel();
+        let (done, stopped) = mpsc::channel();
+        thread::spawn(move || {
+            let handle = ChangeDetection::path(&src).watch(|_| {}).unwrap();
+            stoppers.send(handle.stopper()).unwrap();
+            handle.run().unwrap();
+            done.send(()).unwrap();
+        });
+
+        stopper.recv().unwrap().stop();
+        stopped.recv_timeout(Duration::from_secs(10)).unwrap();
+    }
+
+    fn poll_until_delivered(handle: &mut super::WatchHandle) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !handle.poll(Duration::from_millis(500)).unwrap() {
+            assert!(Instant::now() < deadline, "no changes delivered in time");
+        }
+    }
+}
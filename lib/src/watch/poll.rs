@@ -0,0 +1,132 @@
+use super::{Change, ChangeKind, Skip};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Interval between two scans of watched paths.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// A backend comparing modification times of watched paths.
+pub(super) struct Backend {
+    roots: Vec<PathBuf>,
+    snapshot: Snapshot,
+    waker: Waker,
+    skip: Skip,
+}
+
+/// Wakes up a [`Backend`] waiting for changes, from any thread.
+#[derive(Debug, Clone, Default)]
+pub(super) struct Waker(Arc<(Mutex<bool>, Condvar)>);
+
+impl Waker {
+    pub(super) fn wake(&self) {
+        let (woken, condvar) = &*self.0;
+        *woken.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    /// Waits at most `timeout` for a wake up, returns `true` if there was one.
+    fn sleep(&self, timeout: Duration) -> bool {
+        let (woken, condvar) = &*self.0;
+        let (mut woken, _) = condvar
+            .wait_timeout_while(woken.lock().unwrap(), timeout, |woken| !*woken)
+            .unwrap();
+
+        std::mem::replace(&mut *woken, false)
+    }
+}
+
+impl Backend {
+    pub(super) fn new(roots: &[PathBuf], skip: Skip) -> io::Result<Backend> {
+        Ok(Backend {
+            roots: roots.to_vec(),
+            snapshot: snapshot(roots, &skip)?,
+            waker: Waker::default(),
+            skip,
+        })
+    }
+
+    pub(super) fn waker(&self) -> Waker {
+        self.waker.clone()
+    }
+
+    /// Returns changes, waiting for them at most `timeout` or forever if it is `None`.
+    ///
+    /// Returns no changes right away when woken up with a [`Waker`].
+    pub(super) fn next(&mut self, timeout: Option<Duration>) -> io::Result<Vec<Change>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        loop {
+            if self.waker.sleep(POLL_INTERVAL) {
+                return Ok(vec![]);
+            }
+
+            let current = snapshot(&self.roots, &self.skip)?;
+            let mut changes = vec![];
+            for (path, stamp) in &current {
+                let kind = match self.snapshot.get(path) {
+                    None => ChangeKind::Added,
+                    Some(old) if old != stamp => ChangeKind::Modified,
+                    Some(_) => continue,
+                };
+                changes.push(Change {
+                    path: path.clone(),
+                    kind,
+                });
+            }
+            for path in self.snapshot.keys() {
+                if !current.contains_key(path) {
+                    changes.push(Change {
+                        path: path.clone(),
+                        kind: ChangeKind::Removed,
+                    });
+                }
+            }
+            self.snapshot = current;
+
+            if !changes.is_empty() || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(changes);
+            }
+        }
+    }
+}
+
+fn snapshot(roots: &[PathBuf], skip: &Skip) -> io::Result<Snapshot> {
+    let mut result = Snapshot::new();
+    for path in roots {
+        scan(path, skip, &mut result)?;
+    }
+
+    Ok(result)
+}
+
+/// Adds `path` and everything inside it to `snapshot`, leaving out skipped directories.
+fn scan(path: &Path, skip: &Skip, snapshot: &mut Snapshot) -> io::Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    if metadata.is_dir() && skip(path) {
+        return Ok(());
+    }
+
+    snapshot.insert(
+        path.to_path_buf(),
+        (metadata.modified().ok(), metadata.len()),
+    );
+
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            scan(&entry?.path(), skip, snapshot)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// Writes `contents` to the file at `path` unless it already has exactly these contents.
+///
+/// Unlike [`std::fs::write`] it keeps the modification time of an unchanged file,
+/// so a build script writing into tracked or downstream files doesn't trigger
+/// another rebuild. Returns `true` if the file was written.
+///
+/// Accepts anything convertible to bytes, like `&str`, `String` or `Vec<u8>`.
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::write_if_changed;
+/// if write_if_changed("web/package-lock.json", r#"{"version":"0.1.0"}"#).unwrap() {
+///     println!("cargo:warning=package-lock.json was updated");
+/// }
+/// ```
+pub fn write_if_changed<P, C>(path: P, contents: C) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+    C: AsRef<[u8]>,
+{
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+
+    if has_contents(path, contents)? {
+        return Ok(false);
+    }
+
+    fs::write(path, contents)?;
+
+    Ok(true)
+}
+
+/// Writes everything `f` writes to the file at `path` unless it already has exactly these contents.
+///
+/// See [`write_if_changed`].
+///
+/// # Examples:
+///
+/// ```no_run
+/// # use change_detection::write_with_if_changed;
+/// # use std::io::Write;
+/// write_with_if_changed("web/dist/app/index.js", |out| {
+///     writeln!(out, "let a = {};", 1)
+/// })
+/// .unwrap();
+/// ```
+pub fn write_with_if_changed<P, F>(path: P, f: F) -> io::Result<bool>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let mut contents = vec![];
+    f(&mut contents)?;
+
+    write_if_changed(path, contents)
+}
+
+fn has_contents(path: &Path, contents: &[u8]) -> io::Result<bool> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    if file.metadata()?.len() != contents.len() as u64 {
+        return Ok(false);
+    }
+
+    let mut existing = Vec::with_capacity(contents.len());
+    file.read_to_end(&mut existing)?;
+
+    Ok(existing == contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_if_changed, write_with_if_changed};
+    use crate::test_utils::TempDir;
+    use std::fs;
+
+    #[test]
+    fn writes_only_changed_contents() {
+        let tmp = TempDir::new("write-if-changed");
+        let path = tmp.path().join("index.js");
+
+        assert!(write_if_changed(&path, "let a = 1;").unwrap());
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+        assert!(!write_if_changed(&path, b"let a = 1;").unwrap());
+        assert!(!write_with_if_changed(&path, |out| write!(out, "let a = {};", 1)).unwrap());
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+
+        assert!(write_if_changed(&path, String::from("let a = 2;")).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "let a = 2;");
+    }
+}
@@ -1,6 +1,6 @@
 use change_detection::{
     path_matchers::{equal, func, PathMatcherExt},
-    ChangeDetection,
+    write_if_changed, ChangeDetection,
 };
 use std::{
     env, fs,
@@ -11,7 +11,7 @@ use std::{
 fn main() -> Result<()> {
     let web_pathbuf = PathBuf::from("web");
 
-    ChangeDetection::path_exclude(
+    let guard = ChangeDetection::path_exclude(
         web_pathbuf.clone(),
         equal(web_pathbuf.clone())
             .or(equal(web_pathbuf.join("package-lock.json")))
@@ -20,7 +20,10 @@ fn main() -> Result<()> {
                     || (p.is_file() && p.parent() != Some(web_pathbuf.as_path()))
             })),
     )
-    .generate();
+    .output("web/dist")
+    .output("web/package-lock.json")
+    .deny_overlapping_outputs()
+    .guard();
 
     let out_dir = env::var("OUT_DIR").unwrap();
     let generated_file = Path::new(&out_dir).join("generated.in");
@@ -35,8 +38,10 @@ fn main() -> Result<()> {
 
     fs::write(generated_file, version.to_string())?;
 
-    fs::write("web/package-lock.json", r#"{"version":"0.1.0"}"#)?;
-    fs::write("web/dist/app/index.js", r#"let a = 1;"#)?;
+    write_if_changed("web/package-lock.json", r#"{"version":"0.1.0"}"#)?;
+    write_if_changed("web/dist/app/index.js", r#"let a = 1;"#)?;
+
+    guard.deny().finish()?;
 
     Ok(())
 }
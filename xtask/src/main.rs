@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use anyhow::{anyhow, Result};
 use pico_args::Arguments;
 use std::{